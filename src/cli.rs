@@ -1,167 +1,595 @@
-use crate::exclude::{is_connection_excluded, is_device_excluded, is_socket_excluded};
-use crate::model::{Connection, Interface, Process};
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::process::Command;
-
-pub fn get_hostname(host: &String) -> String {
-    let mut cmd = Command::new("ssh");
-    cmd.arg(&host).arg("hostname");
-    log::debug!("Cmd: {:?}", cmd);
-    let output = cmd.output().expect("cannot call 'hostname' command");
-    String::from_utf8(output.stdout)
-        .expect("cannot convert cmd output to string")
-        .trim()
-        .to_string()
-}
-
-pub fn get_interfaces(host: &String, excludes: &Option<String>) -> Vec<Interface> {
-    let mut interfaces = vec![];
-    let mut cmd = Command::new("ssh");
-    cmd.arg(&host)
-        .arg("ip")
-        .arg("--brief")
-        .arg("address")
-        .arg("show");
-    log::debug!("Cmd: {:?}", cmd);
-    let output = cmd.output().expect("cannot call 'ip a' command");
-    for line in String::from_utf8(output.stdout)
-        .expect("cannot convert cmd output to string")
-        .lines()
-    {
-        let mut interface = Interface {
-            name: "".to_string(),
-            addresses: vec![],
-        };
-        for (index, field) in line.split_whitespace().enumerate() {
-            match index {
-                0 => interface.name = field.to_string(),
-                1 => {}
-                _ => interface.addresses.push(field.to_string()),
-            }
+use crate::exclude::{is_connection_excluded, is_socket_excluded};
+use crate::model::{Connection, Namespace, Process, SocketUser};
+use crate::netns::{NetNs, ROOT};
+use crate::transport::{shell_quote, Transport, TransportError};
+use crate::{
+    container, firewall, link, lsof, neighbor, netns, netstat, procinfo, procnet, route, ss,
+    systemd, unix,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+pub fn run(transport: &dyn Transport, command: &str) -> Result<String, TransportError> {
+    let output = transport.run(command)?;
+    if output.status != Some(0) {
+        log::warn!(
+            "'{}' exited with {:?}: {}",
+            command,
+            output.status,
+            output.stderr.trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Why part of a host could not be collected - the rest of the host is kept.
+#[derive(Debug)]
+pub enum CollectError {
+    /// a session failed - `context` tells which part of the host is missing
+    Transport {
+        context: String,
+        error: TransportError,
+    },
+    /// a section is empty or cannot be parsed
+    Section { name: String, message: String },
+}
+
+impl fmt::Display for CollectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollectError::Transport { context, error } => write!(f, "{}: {}", context, error),
+            CollectError::Section { name, message } => write!(f, "{}: {}", name, message),
         }
-        if !is_device_excluded(&host, &interface.name, &excludes) {
-            interfaces.push(interface);
+    }
+}
+
+/// States of connected sockets in the `ss` notation - the collectors map their names to these.
+pub const CONNECTED_STATES: [&str; 9] = [
+    "ESTAB",
+    "SYN-SENT",
+    "SYN-RECV",
+    "FIN-WAIT-1",
+    "FIN-WAIT-2",
+    "TIME-WAIT",
+    "CLOSE-WAIT",
+    "LAST-ACK",
+    "CLOSING",
+];
+
+/// Connection states to collect, e.g. `ESTAB,SYN-SENT,CLOSE-WAIT`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct States(pub Vec<String>);
+
+impl FromStr for States {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut states = vec![];
+        for state in input.split(',') {
+            let state = state.trim().to_uppercase().replace('_', "-");
+            if !CONNECTED_STATES.contains(&state.as_str()) {
+                return Err(format!(
+                    "invalid connection state '{}' - expected {}",
+                    state,
+                    CONNECTED_STATES.join(", ")
+                ));
+            }
+            states.push(state);
         }
+        Ok(States(states))
     }
-    interfaces
 }
 
-fn extract_proc_name(input: &str) -> String {
-    lazy_static! {
-        static ref RE: Regex = Regex::new("\"(?P<proc>[[:word:]]+)\"").unwrap();
+/// Sections of tools that always print a header - no output means that the tool is missing or
+/// failed.
+fn check_output(
+    sections: &HashMap<String, String>,
+    name: &str,
+    errors: &mut Vec<CollectError>,
+) -> bool {
+    if section(sections, name).trim().is_empty() {
+        errors.push(CollectError::Section {
+            name: name.to_string(),
+            message: String::from("no output - command missing or failed"),
+        });
+        return false;
     }
-    match RE
-        .captures(input)
-        .and_then(|cap| cap.name("proc").map(|proc| proc.as_str()))
-    {
-        None => String::from(""),
-        Some(x) => x.to_string(),
+    true
+}
+
+/// A socket as reported by one of the collectors - addresses use the `ss` notation.
+#[derive(Debug, Clone, Default)]
+pub struct Socket {
+    pub protocol: String,
+    pub state: String,
+    pub local_addr: String,
+    pub local_port: String,
+    pub remote_addr: String,
+    pub remote_port: String,
+    pub users: Vec<SocketUser>,
+}
+
+/// `protocol state local:port remote:port`
+impl fmt::Display for Socket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}:{} {}:{}",
+            self.protocol,
+            self.state,
+            self.local_addr,
+            self.local_port,
+            self.remote_addr,
+            self.remote_port
+        )
+    }
+}
+
+/// Source of the socket tables of a host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collector {
+    /// first available of ss, netstat and lsof - /proc/net otherwise
+    Auto,
+    /// `ss` from iproute2
+    Ss,
+    /// `/proc/net/{tcp,tcp6,udp,udp6}` and `/proc/<pid>/fd`
+    Proc,
+    /// `netstat` from net-tools or busybox
+    Netstat,
+    /// `lsof -i`
+    Lsof,
+}
+
+impl FromStr for Collector {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "auto" => Ok(Collector::Auto),
+            "ss" => Ok(Collector::Ss),
+            "proc" => Ok(Collector::Proc),
+            "netstat" => Ok(Collector::Netstat),
+            "lsof" => Ok(Collector::Lsof),
+            _ => Err(format!(
+                "invalid collector '{}' - expected auto, ss, proc, netstat or lsof",
+                input
+            )),
+        }
     }
 }
 
-fn extract_addr_and_port(endpoint: &String, bindaddr: &mut String, port: &mut String) {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"(?P<bindaddr>.+):(?P<port>\d{1,5})").unwrap();
+/// Script printing the output of each command after a marker line - `prefix` is prepended to the
+/// section names to keep the sections of several namespaces apart.
+pub fn section_script(prefix: &str, sections: &[(&str, &str)]) -> String {
+    let mut script = String::new();
+    for (name, command) in sections {
+        script.push_str(&format!(
+            "echo {}; {{ {}; }} 2>/dev/null; ",
+            shell_quote(&format!("{}{}{}", SECTION_MARKER, prefix, name)),
+            command
+        ));
     }
-    if let Some(cap) = RE.captures(endpoint) {
-        if let Some(addcapture) = cap.name("bindaddr") {
-            *bindaddr = addcapture.as_str().to_string();
+    script
+}
+
+/// Run a script built from `section_script` in one session and split its output into sections.
+pub fn run_script(
+    transport: &dyn Transport,
+    script: &str,
+) -> Result<HashMap<String, String>, TransportError> {
+    // the exit status of the script is the one of the last section
+    let output = run(transport, &format!("{}true", script))?;
+    let mut result: HashMap<String, String> = HashMap::new();
+    let mut current: Option<String> = None;
+    for line in output.lines() {
+        if let Some(name) = line.strip_prefix(SECTION_MARKER) {
+            current = Some(name.to_string());
+            result.entry(name.to_string()).or_default();
+        } else if let Some(name) = &current {
+            let section = result.entry(name.to_string()).or_default();
+            section.push_str(line);
+            section.push('\n');
         }
-        if let Some(portcapture) = cap.name("port") {
-            *port = portcapture.as_str().to_string();
+    }
+    Ok(result)
+}
+
+const SECTION_MARKER: &str = "==flowdot==";
+
+/// Output of a section returned by `run_script` - empty if the section is missing.
+pub fn section<'a>(sections: &'a HashMap<String, String>, name: &str) -> &'a str {
+    sections.get(name).map_or("", |content| content.as_str())
+}
+
+/// The sections whose name starts with `prefix` - with the prefix removed.
+pub fn subsections(sections: &HashMap<String, String>, prefix: &str) -> HashMap<String, String> {
+    sections
+        .iter()
+        .filter_map(|(name, content)| {
+            name.strip_prefix(prefix)
+                .map(|name| (name.to_string(), content.to_string()))
+        })
+        .collect()
+}
+
+/// Merge sockets reported once per process (lsof) into one socket with several users.
+fn merge_sockets(sockets: Vec<Socket>) -> Vec<Socket> {
+    let mut merged: Vec<Socket> = vec![];
+    let mut indexes: HashMap<String, usize> = HashMap::new();
+    for socket in sockets {
+        // the protocol, state and both endpoints - users are not part of it
+        let key = socket.to_string();
+        match indexes.get(&key) {
+            Some(index) => {
+                let existing = &mut merged[*index];
+                for user in socket.users {
+                    if !existing.users.contains(&user) {
+                        existing.users.push(user);
+                    }
+                }
+            }
+            None => {
+                indexes.insert(key, merged.len());
+                merged.push(socket);
+            }
         }
     }
+    merged
 }
 
-pub fn get_processes(host: &String, excludes: &Option<String>) -> Vec<Process> {
-    let mut processes = vec![];
-    let mut cmd = Command::new("ssh");
-    cmd.arg(&host).arg("ss").arg("-tulpn");
-    log::debug!("Cmd: {:?}", cmd);
-    let output = cmd.output().expect("cannot call 'ss -tulpn' command");
-    for line in String::from_utf8(output.stdout)
-        .expect("cannot convert cmd output to string")
-        .lines()
-    {
-        if line.contains(" LISTEN ") || line.contains(" UNCONN ") {
-            let mut bindaddr = String::from("");
-            let mut port = String::from("");
-            let mut protocol = String::from("");
-            let mut procname = String::from("");
-            for (index, field) in line.split_whitespace().enumerate() {
-                match index {
-                    0 => protocol = field.to_string(),
-                    4 => extract_addr_and_port(&field.to_string(), &mut bindaddr, &mut port),
-                    6 => procname = extract_proc_name(field),
-                    _ => {}
+/// Commands to collect the socket tables - listeners and connections come from the same
+/// snapshot.
+fn socket_sections(collector: Collector) -> Vec<(&'static str, &'static str)> {
+    match collector {
+        Collector::Ss => vec![("ss", "ss -tuapn")],
+        Collector::Netstat => vec![("netstat", "netstat -tuapnW")],
+        Collector::Lsof => vec![("lsof", "lsof -i -n -P")],
+        Collector::Proc | Collector::Auto => procnet::SECTIONS.to_vec(),
+    }
+}
+
+/// Script collecting the socket tables - with `Collector::Auto` the host picks the first
+/// available tool, the name of its section tells which one was used.
+fn socket_script(prefix: &str, collector: Collector) -> String {
+    if collector != Collector::Auto {
+        return section_script(prefix, &socket_sections(collector));
+    }
+    let mut script = String::new();
+    for (tool, collector) in [
+        ("ss", Collector::Ss),
+        ("netstat", Collector::Netstat),
+        ("lsof", Collector::Lsof),
+    ] {
+        script.push_str(&format!(
+            "if command -v {} >/dev/null 2>&1; then {}el",
+            tool,
+            section_script(prefix, &socket_sections(collector))
+        ));
+    }
+    script.push_str(&format!(
+        "se {}fi; ",
+        section_script(prefix, &socket_sections(Collector::Proc))
+    ));
+    script
+}
+
+/// The collector whose output is found in the sections - resolves `Collector::Auto`.
+fn detect_collector(collector: Collector, sections: &HashMap<String, String>) -> Collector {
+    if collector != Collector::Auto {
+        return collector;
+    }
+    let collector = if sections.contains_key("ss") {
+        Collector::Ss
+    } else if sections.contains_key("netstat") {
+        Collector::Netstat
+    } else if sections.contains_key("lsof") {
+        Collector::Lsof
+    } else {
+        Collector::Proc
+    };
+    log::info!("Using collector {:?}", collector);
+    collector
+}
+
+fn parse_socket_sections(
+    collector: Collector,
+    sections: &HashMap<String, String>,
+    errors: &mut Vec<CollectError>,
+) -> Vec<Socket> {
+    match collector {
+        Collector::Ss if check_output(sections, "ss", errors) => {
+            match ss::parse_sockets(section(sections, "ss"), "tcp") {
+                Ok(sockets) => sockets,
+                Err(err) => {
+                    errors.push(CollectError::Section {
+                        name: String::from("ss"),
+                        message: err.to_string(),
+                    });
+                    vec![]
                 }
             }
-            let mut process = Process {
-                name: procname,
-                addresses: vec![],
-            };
-            if !is_socket_excluded(&host, &bindaddr, &port, &protocol, &excludes) {
-                let address = String::from("") + &bindaddr + ":" + &port + "/" + &protocol;
-                process.addresses.push(address);
+        }
+        Collector::Netstat if check_output(sections, "netstat", errors) => {
+            netstat::parse_sockets(section(sections, "netstat"))
+        }
+        // lsof prints nothing without sockets and a line per process sharing a socket
+        Collector::Lsof => merge_sockets(lsof::parse_sockets(section(sections, "lsof"))),
+        Collector::Proc | Collector::Auto if check_output(sections, "tcp", errors) => {
+            procnet::parse_sockets(sections)
+        }
+        _ => vec![],
+    }
+}
+
+/// Script collecting the interfaces, routes, neighbors, firewall rules, sockets and unix sockets
+/// of a namespace.
+fn namespace_script(prefix: &str, collector: Collector) -> String {
+    let mut script = section_script(prefix, &link::SECTIONS);
+    script.push_str(&section_script(prefix, &route::SECTIONS));
+    script.push_str(&section_script(prefix, &neighbor::SECTIONS));
+    script.push_str(&section_script(prefix, &firewall::SECTIONS));
+    script.push_str(&section_script(prefix, &unix::SECTIONS));
+    script.push_str(&socket_script(prefix, collector));
+    script
+}
+
+/// Script run once per host: hostname, namespaces, the root namespace and the details of the
+/// processes. The process details are shared by all namespaces of the host and follow the socket
+/// tables, only the owners of sockets are collected.
+pub fn host_script(collector: Collector, namespaces: bool) -> String {
+    let mut script = section_script("", &[("hostname", "hostname"), ("uid", "id -u")]);
+    if namespaces {
+        script.push_str(&section_script("", &netns::SECTIONS));
+    }
+    script.push_str(&section_script("", &container::SECTIONS));
+    script.push_str(&namespace_script("", collector));
+    script.push_str(&section_script("", &procinfo::SECTIONS));
+    script
+}
+
+/// Run a script as root with `sudo -n` - if sudo is missing or asks for a password the script
+/// runs as the login user and a `nosudo` section is added.
+pub fn sudo_script(script: &str) -> String {
+    let quoted = shell_quote(&format!("{}true", script));
+    format!(
+        "if sudo -n true >/dev/null 2>&1; then sudo -n sh -c {}; else {}sh -c {}; fi; ",
+        quoted,
+        section_script("", &[("nosudo", "true")]),
+        quoted
+    )
+}
+
+/// Script collecting the other namespaces of a host - the sections of each namespace are
+/// prefixed with `NetNs::section_prefix`.
+pub fn namespaces_script(namespaces: &[NetNs], collector: Collector) -> String {
+    let mut script = String::new();
+    for namespace in namespaces {
+        if let Some(prefix) = &namespace.prefix {
+            script.push_str(&format!(
+                "{} sh -c {}; ",
+                prefix,
+                shell_quote(&namespace_script(&namespace.section_prefix(), collector))
+            ));
+        }
+    }
+    script
+}
+
+/// Listening sockets aggregated per process - sockets shared by several processes (pre-forked
+/// workers) are added to each of them.
+fn parse_processes(sockets: &[Socket], host: &str, excludes: &Option<String>) -> Vec<Process> {
+    let mut processes: Vec<Process> = vec![];
+    for socket in sockets {
+        if (socket.state == "LISTEN" || socket.state == "UNCONN")
+            && !is_socket_excluded(
+                host,
+                &socket.local_addr,
+                &socket.local_port,
+                &socket.protocol,
+                excludes,
+            )
+        {
+            let address = String::from("")
+                + &socket.local_addr
+                + ":"
+                + &socket.local_port
+                + "/"
+                + &socket.protocol;
+            let mut users = socket.users.clone();
+            if users.is_empty() {
+                users.push(SocketUser {
+                    name: String::from(""),
+                    pid: None,
+                    fd: None,
+                    unit: None,
+                });
             }
             // TODO: extra parameter to exclude specific processes
-            if process.addresses.len() > 0 {
-                processes.push(process);
+            for user in users {
+                let existing = processes
+                    .iter_mut()
+                    .find(|process| process.pid == user.pid && process.name == user.name);
+                match existing {
+                    Some(process) => {
+                        if !process.addresses.contains(&address) {
+                            process.addresses.push(address.to_string());
+                        }
+                    }
+                    None => processes.push(Process {
+                        name: user.name,
+                        pid: user.pid,
+                        addresses: vec![address.to_string()],
+                        ..Default::default()
+                    }),
+                }
             }
         }
     }
     processes
 }
 
-pub fn get_connections(host: &String, excludes: &Option<String>) -> Vec<Connection> {
+fn parse_connections(
+    sockets: Vec<Socket>,
+    host: &str,
+    excludes: &Option<String>,
+    states: &States,
+) -> Vec<Connection> {
     let mut connections = vec![];
-    let mut cmd = Command::new("ssh");
-    cmd.arg(&host).arg("ss").arg("-tuapn");
-    log::debug!("Cmd: {:?}", cmd);
-    let output = cmd.output().expect("cannot call 'ss -tuapn' command");
-    for line in String::from_utf8(output.stdout)
-        .expect("cannot convert cmd output to string")
-        .lines()
-    {
-        if line.contains(" ESTAB ") {
-            let mut localaddr = String::from("");
-            let mut localport = String::from("");
-            let mut remoteaddr = String::from("");
-            let mut remoteport = String::from("");
-            let mut procname = String::from("");
-            let mut protocol = String::from("");
-            for (index, field) in line.split_whitespace().enumerate() {
-                match index {
-                    0 => protocol = field.to_string(),
-                    4 => extract_addr_and_port(&field.to_string(), &mut localaddr, &mut localport),
-                    5 => {
-                        extract_addr_and_port(&field.to_string(), &mut remoteaddr, &mut remoteport)
-                    }
-                    6 => procname = extract_proc_name(field),
-                    _ => {}
-                }
-            }
-            let connection = Connection {
+    for socket in sockets {
+        if states.0.contains(&socket.state)
+            && !is_connection_excluded(
+                host,
+                &socket.remote_addr,
+                &socket.remote_port,
+                &socket.local_addr,
+                &socket.local_port,
+                &socket.protocol,
+                excludes,
+            )
+        {
+            connections.push(Connection {
                 host: host.to_string(),
-                process: procname.to_string(),
-                local_addr: localaddr.to_string(),
-                local_port: localport.to_string(),
-                remote_addr: remoteaddr.to_string(),
-                remote_port: remoteport.to_string(),
-            };
-            if !is_connection_excluded(
-                &host,
-                &remoteaddr,
-                &remoteport,
-                &localaddr,
-                &localport,
-                &protocol,
-                &excludes,
-            ) {
-                connections.push(connection);
-            }
+                namespace: String::from(ROOT),
+                process: socket
+                    .users
+                    .first()
+                    .map_or(String::from(""), |user| user.name.to_string()),
+                users: socket.users,
+                local_addr: socket.local_addr,
+                local_port: socket.local_port,
+                remote_addr: socket.remote_addr,
+                remote_port: socket.remote_port,
+                state: socket.state,
+            });
         }
     }
     connections
 }
+
+/// Interfaces, routes, neighbors, firewall, listening processes, unix sockets and connections
+/// in one of `states` of a namespace from the sections of `namespace_script` - without the
+/// prefix of the namespace. Failed sections are added to `errors`, the others are still parsed.
+pub fn parse_namespace(
+    sections: &HashMap<String, String>,
+    netns: &NetNs,
+    host: &str,
+    excludes: &Option<String>,
+    collector: Collector,
+    states: &States,
+    errors: &mut Vec<CollectError>,
+) -> (Namespace, Vec<Connection>) {
+    check_output(sections, "interfaces", errors);
+    let interfaces = match link::parse_interfaces(section(sections, "interfaces"), host, excludes) {
+        Ok(interfaces) => interfaces,
+        Err(err) => {
+            errors.push(CollectError::Section {
+                name: String::from("interfaces"),
+                message: err.to_string(),
+            });
+            vec![]
+        }
+    };
+    let (routes, rules) = route::parse_sections(sections);
+    let neighbors = neighbor::parse_neighbors(section(sections, "neighbors"), host, excludes);
+    let sockets = parse_socket_sections(detect_collector(collector, sections), sections, errors);
+    let mut processes = parse_processes(&sockets, host, excludes);
+    let firewall = firewall::parse_sections(sections);
+    firewall::annotate(&firewall, &mut processes);
+    let mut connections = parse_connections(sockets, host, excludes, states);
+    for connection in &mut connections {
+        connection.namespace = netns.name.to_string();
+    }
+    let namespace = Namespace {
+        name: netns.name.to_string(),
+        inode: netns.inode,
+        interfaces,
+        routes,
+        rules,
+        neighbors,
+        firewall,
+        processes,
+        unix_sockets: unix::parse_unix_sockets(section(sections, "unix")),
+    };
+    (namespace, connections)
+}
+
+/// Add the details of `procinfo::SECTIONS` and `container::SECTIONS` to the processes of all
+/// namespaces and to the socket users.
+pub fn add_process_info(
+    sections: &HashMap<String, String>,
+    namespaces: &mut [Namespace],
+    connections: &mut [Connection],
+) {
+    let infos = procinfo::parse_sections(sections);
+    let containers = container::parse_sections(sections);
+    for process in namespaces.iter_mut().flat_map(|ns| ns.processes.iter_mut()) {
+        if let Some(info) = process.pid.and_then(|pid| infos.get(&pid)) {
+            process.ppid = info.ppid;
+            process.uid = info.uid;
+            process.user = info.user.clone();
+            process.exe = info.exe.clone();
+            process.cmdline = info.cmdline.clone();
+            process.start_time = info.start_time;
+            process.container = container::parse_cgroups(&info.cgroups);
+            process.unit = systemd::parse_unit(&info.cgroups);
+        }
+        if let Some(container) = &mut process.container {
+            if let Some((name, image)) = containers.get(&container.id) {
+                container.name = Some(name.to_string());
+                container.image = Some(image.to_string());
+            }
+        }
+    }
+    let unix_sockets = namespaces
+        .iter_mut()
+        .flat_map(|ns| ns.unix_sockets.iter_mut());
+    for users in connections
+        .iter_mut()
+        .map(|connection| &mut connection.users)
+        .chain(unix_sockets.map(|socket| &mut socket.users))
+    {
+        for user in users {
+            user.unit = user
+                .pid
+                .and_then(|pid| infos.get(&pid))
+                .and_then(|info| systemd::parse_unit(&info.cgroups));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socket(state: &str, remote_port: &str, user: &str, pid: u32) -> Socket {
+        Socket {
+            protocol: String::from("tcp"),
+            state: state.to_string(),
+            local_addr: String::from("10.0.0.5"),
+            local_port: String::from("22"),
+            remote_addr: String::from("10.0.0.9"),
+            remote_port: remote_port.to_string(),
+            users: vec![SocketUser {
+                name: user.to_string(),
+                pid: Some(pid),
+                fd: Some(3),
+                unit: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn merges_sockets_of_several_processes() {
+        let merged = merge_sockets(vec![
+            socket("ESTAB", "51234", "sshd", 1404),
+            socket("ESTAB", "51235", "sshd", 1500),
+            socket("ESTAB", "51234", "sshd", 1406),
+            socket("ESTAB", "51234", "sshd", 1404),
+        ]);
+        assert_eq!(merged.len(), 2);
+        let pids: Vec<_> = merged[0].users.iter().map(|user| user.pid).collect();
+        assert_eq!(pids, vec![Some(1404), Some(1406)]);
+        assert_eq!(merged[1].remote_port, "51235");
+    }
+}
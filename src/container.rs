@@ -0,0 +1,129 @@
+use crate::cli::section;
+use crate::model::Container;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Names and images of the running containers - fails silently without docker/podman access.
+pub const SECTIONS: [(&str, &str); 2] = [
+    (
+        "docker",
+        "docker ps --no-trunc --format '{{.ID}} {{.Names}} {{.Image}}'",
+    ),
+    (
+        "podman",
+        "podman ps --no-trunc --format '{{.ID}} {{.Names}} {{.Image}}'",
+    ),
+];
+
+/// Find the container of a process in its `/proc/<pid>/cgroup` paths, e.g.
+/// `0::/system.slice/docker-<id>.scope`, `12:pids:/docker/<id>` or
+/// `0::/kubepods.slice/.../cri-containerd-<id>.scope`.
+pub fn parse_cgroups(cgroups: &[String]) -> Option<Container> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"[-/](?P<id>[0-9a-f]{64})(\.scope)?(/|$)").unwrap();
+    }
+    for cgroup in cgroups {
+        if let Some(cap) = RE.captures(cgroup) {
+            let runtime = if cgroup.contains("libpod") {
+                "podman"
+            } else if cgroup.contains("crio") {
+                "cri-o"
+            } else if cgroup.contains("containerd") || cgroup.contains("kubepods") {
+                "containerd"
+            } else if cgroup.contains("docker") {
+                "docker"
+            } else {
+                "unknown"
+            };
+            return Some(Container {
+                id: cap["id"].to_string(),
+                runtime: runtime.to_string(),
+                name: None,
+                image: None,
+            });
+        }
+    }
+    None
+}
+
+/// Parse `{docker,podman} ps --format '{{.ID}} {{.Names}} {{.Image}}'` - returns name and image
+/// per container id.
+pub fn parse_ps(content: &str) -> HashMap<String, (String, String)> {
+    let mut containers = HashMap::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if let [id, name, image] = fields[..] {
+            containers.insert(id.to_string(), (name.to_string(), image.to_string()));
+        }
+    }
+    containers
+}
+
+/// Names and images of the `SECTIONS` output by container id.
+pub fn parse_sections(sections: &HashMap<String, String>) -> HashMap<String, (String, String)> {
+    let mut containers = parse_ps(section(sections, "docker"));
+    containers.extend(parse_ps(section(sections, "podman")));
+    containers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "3f4e8b7c2a1d9e6f5b4c3a2d1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f";
+
+    fn runtime(cgroup: &str) -> Option<String> {
+        parse_cgroups(&[String::from("1:name=systemd:/"), cgroup.replace("ID", ID)])
+            .map(|container| format!("{} {}", container.runtime, container.id))
+    }
+
+    #[test]
+    fn finds_containers_in_cgroups() {
+        let expected = |runtime: &str| Some(format!("{} {}", runtime, ID));
+        assert_eq!(
+            runtime("0::/system.slice/docker-ID.scope"),
+            expected("docker")
+        );
+        assert_eq!(runtime("12:pids:/docker/ID"), expected("docker"));
+        assert_eq!(
+            runtime("0::/machine.slice/libpod-ID.scope/container"),
+            expected("podman")
+        );
+        assert_eq!(
+            runtime("0::/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod1.slice/cri-containerd-ID.scope"),
+            expected("containerd")
+        );
+        assert_eq!(
+            runtime("0::/kubepods.slice/kubepods-pod1.slice/crio-ID.scope"),
+            expected("cri-o")
+        );
+        assert_eq!(
+            runtime("0::/user.slice/user-1000.slice/session-3.scope"),
+            None
+        );
+        // ids are 64 hex digits, shorter ones are no container ids
+        assert_eq!(runtime("0::/system.slice/docker-3f4e8b7c2a1d.scope"), None);
+    }
+
+    #[test]
+    fn parses_ps() {
+        let sections = HashMap::from([
+            (
+                String::from("docker"),
+                format!("{} web nginx:1.25\nmalformed line\n", ID),
+            ),
+            (
+                String::from("podman"),
+                String::from("abc db docker.io/library/postgres:16\n"),
+            ),
+        ]);
+        let containers = parse_sections(&sections);
+        assert_eq!(containers.len(), 2);
+        assert_eq!(
+            containers[ID],
+            (String::from("web"), String::from("nginx:1.25"))
+        );
+        assert_eq!(containers["abc"].0, "db");
+    }
+}
@@ -20,17 +20,16 @@ pub fn is_host_excluded(host: &str, excludes: &Option<String>) -> bool {
     let mut excluded = false;
     if let Some(excludes) = excludes {
         for exclude in excludes.split(',') {
-            if let Some(cap) = get_exclusion_capture(&exclude) {
+            if let Some(cap) = get_exclusion_capture(exclude) {
                 if let Some(name) = cap.name("host") {
-                    if cap.name("device") == None
-                        && cap.name("port") == None
-                        && cap.name("protocol") == None
+                    if cap.name("device").is_none()
+                        && cap.name("port").is_none()
+                        && cap.name("protocol").is_none()
+                        && name.as_str().eq(host)
                     {
-                        if name.as_str().eq(host) {
-                            log::info!("Excluding host {} - rule: {}", host, exclude);
-                            excluded = true;
-                            break;
-                        }
+                        log::info!("Excluding host {} - rule: {}", host, exclude);
+                        excluded = true;
+                        break;
                     }
                 }
             }
@@ -43,19 +42,20 @@ pub fn is_device_excluded(host: &str, device: &str, excludes: &Option<String>) -
     let mut excluded = false;
     if let Some(excludes) = excludes {
         for exclude in excludes.split(',') {
-            if let Some(cap) = get_exclusion_capture(&exclude) {
+            if let Some(cap) = get_exclusion_capture(exclude) {
                 let hostname = match cap.name("host") {
                     None => "",
                     Some(x) => x.as_str(),
                 };
                 if let Some(devicename) = cap.name("device") {
-                    if cap.name("port") == None && cap.name("protocol") == None {
-                        if (hostname.eq(host) || hostname.eq("")) && devicename.as_str().eq(device)
-                        {
-                            log::info!("Excluding device {}%{} - rule: {}", host, device, exclude);
-                            excluded = true;
-                            break;
-                        }
+                    if cap.name("port").is_none()
+                        && cap.name("protocol").is_none()
+                        && (hostname.eq(host) || hostname.is_empty())
+                        && devicename.as_str().eq(device)
+                    {
+                        log::info!("Excluding device {}%{} - rule: {}", host, device, exclude);
+                        excluded = true;
+                        break;
                     }
                 }
             }
@@ -74,7 +74,7 @@ pub fn is_socket_excluded(
     let mut excluded = false;
     if let Some(excludes) = excludes {
         for exclude in excludes.split(',') {
-            if let Some(cap) = get_exclusion_capture(&exclude) {
+            if let Some(cap) = get_exclusion_capture(exclude) {
                 let hostname = match cap.name("host") {
                     None => "",
                     Some(x) => x.as_str(),
@@ -92,16 +92,16 @@ pub fn is_socket_excluded(
                     Some(x) => x.as_str(),
                 };
                 if ((hostname.eq(host) || hostname.eq(bindaddr))
-                    && portname.eq("")
-                    && devicename.eq("")
-                    && protocolname.eq(""))
-                    || ((hostname.eq(host) || hostname.eq(bindaddr) || hostname.eq(""))
+                    && portname.is_empty()
+                    && devicename.is_empty()
+                    && protocolname.is_empty())
+                    || ((hostname.eq(host) || hostname.eq(bindaddr) || hostname.is_empty())
                         && portname.eq(port)
-                        && devicename.eq("")
-                        && (protocolname.eq(protocol) || protocolname.eq("")))
-                    || ((hostname.eq(host) || hostname.eq(bindaddr) || hostname.eq(""))
-                        && (portname.eq(port) || protocolname.eq(""))
-                        && devicename.eq("")
+                        && devicename.is_empty()
+                        && (protocolname.eq(protocol) || protocolname.is_empty()))
+                    || ((hostname.eq(host) || hostname.eq(bindaddr) || hostname.is_empty())
+                        && (portname.eq(port) || protocolname.is_empty())
+                        && devicename.is_empty()
                         && protocolname.eq(protocol))
                 {
                     log::debug!(
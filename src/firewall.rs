@@ -0,0 +1,769 @@
+use crate::cli::section;
+use crate::model::{FilterChain, FilterRule, Firewall, Process, Reachability, Verdict};
+use ipaddress::IPAddress;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Filter rules - nftables first, iptables for hosts without `nft`. Both need root.
+pub const SECTIONS: [(&str, &str); 3] = [
+    ("nftables", "nft -j list ruleset"),
+    ("iptables", "iptables-save -t filter"),
+    ("ip6tables", "ip6tables-save -t filter"),
+];
+
+/// Jumps followed before a chain is treated as not deciding - chains may jump in circles.
+const MAX_DEPTH: usize = 16;
+
+/// Port ranges of `22`, `1000-2000` (nft) or `1000:2000` (iptables).
+fn parse_port_range(input: &str) -> Option<(u16, u16)> {
+    match input.split_once([':', '-']) {
+        Some((first, last)) => Some((first.parse().ok()?, last.parse().ok()?)),
+        None => input.parse().ok().map(|port| (port, port)),
+    }
+}
+
+/// Values of a nft match - single values, anonymous sets and lists.
+fn nft_values(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(values) => values.iter().collect(),
+        Value::Object(object) => match object.get("set") {
+            Some(Value::Array(values)) => values.iter().collect(),
+            _ => vec![value],
+        },
+        _ => vec![value],
+    }
+}
+
+/// A rule with conditions the evaluation does not know - interfaces, destinations, rate limits -
+/// is skipped if it may let connections pass, it is taken as unconditional if it drops them.
+fn skips_partial(verdict: Option<Verdict>, partial: bool) -> Option<Verdict> {
+    match verdict {
+        Some(Verdict::Accept | Verdict::Jump(_) | Verdict::Goto(_)) if partial => None,
+        verdict => verdict,
+    }
+}
+
+/// Parse a nft rule - none if it never decides for new connections from other hosts.
+fn parse_nft_rule(exprs: &[Value]) -> Option<FilterRule> {
+    let mut rule = FilterRule {
+        protocol: None,
+        sources: vec![],
+        ports: vec![],
+        verdict: Verdict::Return,
+    };
+    let mut verdict = None;
+    let mut partial = false;
+    for expr in exprs {
+        if let Some(m) = expr.get("match") {
+            let (left, right) = (&m["left"], &m["right"]);
+            if m["op"] == "!=" || right.as_str().is_some_and(|name| name.starts_with('@')) {
+                return None;
+            }
+            let values = nft_values(right);
+            if let Some(payload) = left.get("payload") {
+                match (payload["protocol"].as_str(), payload["field"].as_str()) {
+                    (Some(protocol @ ("tcp" | "udp" | "th")), Some("dport")) => {
+                        if protocol != "th" {
+                            rule.protocol = Some(protocol.to_string());
+                        }
+                        for value in values {
+                            let range = match value.get("range") {
+                                Some(range) => Some((
+                                    range[0].as_u64().unwrap_or(0) as u16,
+                                    range[1].as_u64().unwrap_or(0) as u16,
+                                )),
+                                None => value.as_u64().map(|port| (port as u16, port as u16)),
+                            };
+                            rule.ports.extend(range);
+                        }
+                    }
+                    (Some("ip" | "ip6"), Some("saddr")) => {
+                        for value in values {
+                            let source = match value.get("prefix") {
+                                Some(prefix) => prefix["addr"]
+                                    .as_str()
+                                    .map(|addr| format!("{}/{}", addr, prefix["len"])),
+                                None => value.as_str().map(|addr| addr.to_string()),
+                            };
+                            rule.sources.extend(source);
+                        }
+                    }
+                    (Some("ip"), Some("protocol")) | (Some("ip6"), Some("nexthdr")) => {
+                        if let [Value::String(protocol)] = values.as_slice() {
+                            rule.protocol = Some(protocol.to_string());
+                        }
+                    }
+                    _ => partial = true,
+                }
+            } else if let Some(key) = left.pointer("/meta/key").and_then(|key| key.as_str()) {
+                match key {
+                    "l4proto" => {
+                        if let [Value::String(protocol)] = values.as_slice() {
+                            rule.protocol = Some(protocol.to_string());
+                        }
+                    }
+                    "iifname" | "iif" if values.iter().all(|value| *value == "lo") => return None,
+                    _ => partial = true,
+                }
+            } else if left.pointer("/ct/key") == Some(&Value::from("state")) {
+                if !values.iter().any(|value| *value == "new") {
+                    return None;
+                }
+            } else {
+                // e.g. `fib daddr type local`, `ct status dnat` or `meta mark`
+                partial = true;
+            }
+        } else if expr.get("limit").is_some() {
+            partial = true;
+        } else if expr.get("accept").is_some() {
+            verdict = Some(Verdict::Accept);
+        } else if expr.get("drop").is_some() || expr.get("reject").is_some() {
+            verdict = Some(Verdict::Drop);
+        } else if expr.get("return").is_some() {
+            verdict = Some(Verdict::Return);
+        } else if let Some(target) = expr.pointer("/jump/target").and_then(|t| t.as_str()) {
+            verdict = Some(Verdict::Jump(target.to_string()));
+        } else if let Some(target) = expr.pointer("/goto/target").and_then(|t| t.as_str()) {
+            verdict = Some(Verdict::Goto(target.to_string()));
+        }
+    }
+    rule.verdict = skips_partial(verdict, partial)?;
+    Some(rule)
+}
+
+/// Parse `nft -j list ruleset` - filter chains of the input hook and the chains of their tables.
+fn parse_nftables(content: &str) -> Option<Firewall> {
+    let ruleset: Value = serde_json::from_str(content).ok()?;
+    let mut chains: Vec<FilterChain> = vec![];
+    for object in ruleset["nftables"].as_array()? {
+        if let Some(chain) = object.get("chain") {
+            let family = chain["family"].as_str().unwrap_or_default();
+            if !["ip", "ip6", "inet"].contains(&family) {
+                continue;
+            }
+            let input = chain["hook"] == "input" && chain["type"] == "filter";
+            chains.push(FilterChain {
+                family: family.to_string(),
+                table: chain["table"].as_str().unwrap_or_default().to_string(),
+                name: chain["name"].as_str().unwrap_or_default().to_string(),
+                input,
+                priority: chain["prio"].as_i64().unwrap_or(0) as i32,
+                policy: match (input, chain["policy"].as_str()) {
+                    (false, _) => None,
+                    (true, Some("drop")) => Some(Verdict::Drop),
+                    (true, _) => Some(Verdict::Accept),
+                },
+                rules: vec![],
+            });
+        } else if let Some(rule) = object.get("rule") {
+            let found = chains.iter_mut().find(|chain| {
+                rule["family"] == chain.family.as_str()
+                    && rule["table"] == chain.table.as_str()
+                    && rule["chain"] == chain.name.as_str()
+            });
+            if let (Some(chain), Some(exprs)) = (found, rule["expr"].as_array()) {
+                chain.rules.extend(parse_nft_rule(exprs));
+            }
+        }
+    }
+    Some(Firewall {
+        source: String::from("nftables"),
+        chains,
+    })
+}
+
+/// Split an iptables-save line into arguments - comments may be quoted.
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+/// Parse an iptables rule after `-A CHAIN` - none if it never decides for new connections from
+/// other hosts.
+fn parse_iptables_rule(args: &[String], chains: &[FilterChain]) -> Option<FilterRule> {
+    let mut rule = FilterRule {
+        protocol: None,
+        sources: vec![],
+        ports: vec![],
+        verdict: Verdict::Return,
+    };
+    let mut verdict = None;
+    let mut partial = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "!" | "--match-set" | "--src-range" | "--mac-source" => return None,
+            // modules and comments - the options of the modules decide
+            "-m" | "--match" | "--comment" => {
+                args.next()?;
+            }
+            "--syn" => {}
+            "-p" | "--protocol" => {
+                rule.protocol = args
+                    .next()
+                    .filter(|protocol| *protocol != "all")
+                    .map(|protocol| protocol.to_string())
+            }
+            "-s" | "--source" => {
+                let sources = args.next()?;
+                rule.sources
+                    .extend(sources.split(',').map(|s| s.to_string()));
+            }
+            "-i" | "--in-interface" => {
+                let interface = args.next()?;
+                if interface == "lo" {
+                    return None;
+                }
+                partial = true;
+            }
+            "--dport" | "--dports" | "--destination-port" | "--destination-ports" => {
+                let ports = args.next()?;
+                rule.ports
+                    .extend(ports.split(',').filter_map(parse_port_range));
+            }
+            "--ctstate" | "--state" => {
+                let states = args.next()?;
+                if !states.split(',').any(|state| state == "NEW") {
+                    return None;
+                }
+            }
+            "-j" | "--jump" => {
+                verdict = match args.next()?.as_str() {
+                    "ACCEPT" => Some(Verdict::Accept),
+                    "DROP" | "REJECT" => Some(Verdict::Drop),
+                    "RETURN" => Some(Verdict::Return),
+                    // non-terminating targets, e.g. LOG
+                    target if chains.iter().all(|chain| chain.name != target) => return None,
+                    target => Some(Verdict::Jump(target.to_string())),
+                }
+            }
+            "-g" | "--goto" => verdict = Some(Verdict::Goto(args.next()?.to_string())),
+            // e.g. `-d`, `--dst-type`, `--sport` or `--limit` - and the options of targets
+            option if option.starts_with('-') && verdict.is_none() => partial = true,
+            _ => {}
+        }
+    }
+    rule.verdict = skips_partial(verdict, partial)?;
+    Some(rule)
+}
+
+/// Parse `iptables-save -t filter` - `family` is `ip` or `ip6`.
+fn parse_iptables(content: &str, family: &str) -> Vec<FilterChain> {
+    let mut chains: Vec<FilterChain> = vec![];
+    for line in content.lines() {
+        if let Some(declaration) = line.strip_prefix(':') {
+            // `:INPUT DROP [0:0]` - user chains have the policy `-`
+            let mut fields = declaration.split_whitespace();
+            let name = fields.next().unwrap_or_default();
+            let input = name == "INPUT";
+            chains.push(FilterChain {
+                family: family.to_string(),
+                table: String::from("filter"),
+                name: name.to_string(),
+                input,
+                priority: 0,
+                policy: match (input, fields.next()) {
+                    (false, _) => None,
+                    (true, Some("DROP")) => Some(Verdict::Drop),
+                    (true, _) => Some(Verdict::Accept),
+                },
+                rules: vec![],
+            });
+        } else if line.starts_with("-A ") {
+            let args = split_args(line);
+            if let Some(rule) = parse_iptables_rule(&args[2..], &chains) {
+                if let Some(chain) = chains.iter_mut().find(|chain| chain.name == args[1]) {
+                    chain.rules.push(rule);
+                }
+            }
+        }
+    }
+    chains
+}
+
+/// Firewall of the `SECTIONS` output - none if no rules could be read.
+///
+/// nft lists a ruleset even without any tables, iptables is used if it has no input chain - the
+/// legacy iptables rules are not part of the nft ruleset.
+pub fn parse_sections(sections: &HashMap<String, String>) -> Option<Firewall> {
+    let nftables = parse_nftables(section(sections, "nftables"));
+    if let Some(firewall) = &nftables {
+        if firewall.chains.iter().any(|chain| chain.input) {
+            return nftables;
+        }
+    }
+    let mut chains = parse_iptables(section(sections, "iptables"), "ip");
+    chains.extend(parse_iptables(section(sections, "ip6tables"), "ip6"));
+    match chains.is_empty() {
+        // no filter rules at all
+        true => nftables,
+        false => Some(Firewall {
+            source: String::from("iptables"),
+            chains,
+        }),
+    }
+}
+
+/// `network` lies within one of `networks`.
+fn is_network_in(network: &str, networks: &[String]) -> bool {
+    match IPAddress::parse(network) {
+        Ok(inner) => networks.iter().any(|outer| {
+            IPAddress::parse(outer.as_str()).is_ok_and(|outer| outer.includes(&inner))
+        }),
+        Err(_) => networks.iter().any(|outer| outer == network),
+    }
+}
+
+/// Outcome of a chain for new connections to one port.
+enum Outcome {
+    Accept,
+    Drop,
+    /// the chain returns or ends without verdict
+    Continue,
+}
+
+/// Follow the rules of `chain` - sources accepted by rules with source conditions are added to
+/// `accepted`, sources dropped by them to `dropped`. Rules after them still decide for the other
+/// sources.
+fn evaluate_chain(
+    firewall: &Firewall,
+    chain: &FilterChain,
+    protocol: &str,
+    port: u16,
+    accepted: &mut Vec<String>,
+    dropped: &mut Vec<String>,
+    depth: usize,
+) -> Outcome {
+    for rule in &chain.rules {
+        if rule.protocol.as_deref().is_some_and(|p| p != protocol)
+            || (!rule.ports.is_empty()
+                && !rule
+                    .ports
+                    .iter()
+                    .any(|(first, last)| (*first..=*last).contains(&port)))
+        {
+            continue;
+        }
+        let outcome = match &rule.verdict {
+            Verdict::Accept => Outcome::Accept,
+            Verdict::Drop => Outcome::Drop,
+            Verdict::Return => Outcome::Continue,
+            Verdict::Jump(target) | Verdict::Goto(target) => {
+                let target = firewall.chains.iter().find(|other| {
+                    other.family == chain.family
+                        && other.table == chain.table
+                        && other.name == *target
+                });
+                match target {
+                    Some(target) if depth < MAX_DEPTH => {
+                        let (mut nested, mut nested_dropped) = (vec![], vec![]);
+                        let outcome = evaluate_chain(
+                            firewall,
+                            target,
+                            protocol,
+                            port,
+                            &mut nested,
+                            &mut nested_dropped,
+                            depth + 1,
+                        );
+                        if rule.sources.is_empty() {
+                            accepted.extend(nested);
+                            dropped.extend(nested_dropped);
+                            outcome
+                        } else {
+                            // the rule limits the jump to its sources
+                            if let Outcome::Accept = outcome {
+                                accepted.extend(rule.sources.iter().cloned());
+                            }
+                            Outcome::Continue
+                        }
+                    }
+                    _ => Outcome::Continue,
+                }
+            }
+        };
+        match (outcome, rule.sources.is_empty()) {
+            (Outcome::Accept, false) => accepted.extend(rule.sources.iter().cloned()),
+            (Outcome::Accept, true) => return Outcome::Accept,
+            (Outcome::Drop, true) => return Outcome::Drop,
+            // back to the calling chain - a goto does not return to this one
+            (Outcome::Continue, true)
+                if matches!(rule.verdict, Verdict::Return | Verdict::Goto(_)) =>
+            {
+                return Outcome::Continue
+            }
+            // a drop for some sources only - the other sources are decided later, the sources
+            // accepted before stay accepted
+            (Outcome::Drop, false) => dropped.extend(
+                rule.sources
+                    .iter()
+                    .filter(|source| !is_network_in(source, accepted))
+                    .cloned(),
+            ),
+            _ => {}
+        }
+    }
+    Outcome::Continue
+}
+
+/// Split restricted sources into the allowed and the excluded (`!`) networks.
+fn split_sources(sources: &[String]) -> (Vec<String>, Vec<String>) {
+    let (excluded, allowed): (Vec<&String>, Vec<&String>) =
+        sources.iter().partition(|source| source.starts_with('!'));
+    (
+        allowed.into_iter().cloned().collect(),
+        excluded.into_iter().cloned().collect(),
+    )
+}
+
+/// Both results have to allow a connection - every input chain is passed.
+fn combine(first: Reachability, second: Reachability) -> Reachability {
+    match (first, second) {
+        (Reachability::Firewalled, _) | (_, Reachability::Firewalled) => Reachability::Firewalled,
+        (Reachability::Open, other) | (other, Reachability::Open) => other,
+        (Reachability::Restricted(first), Reachability::Restricted(second)) => {
+            let ((first, first_excluded), (second, second_excluded)) =
+                (split_sources(&first), split_sources(&second));
+            // only excluded networks allow any other source
+            let mut sources: Vec<String> = match (first.is_empty(), second.is_empty()) {
+                (true, _) => second,
+                (_, true) => first,
+                (false, false) => {
+                    let mut sources: Vec<String> = vec![];
+                    for source in second.iter().filter(|s| is_network_in(s, &first)) {
+                        sources.push(source.to_string());
+                    }
+                    for source in first.iter().filter(|s| is_network_in(s, &second)) {
+                        if !sources.contains(source) {
+                            sources.push(source.to_string());
+                        }
+                    }
+                    if sources.is_empty() {
+                        return Reachability::Firewalled;
+                    }
+                    sources
+                }
+            };
+            for source in first_excluded.into_iter().chain(second_excluded) {
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+            Reachability::Restricted(sources)
+        }
+    }
+}
+
+/// Reachability of a port for one address family - `ip` or `ip6`.
+fn evaluate_family(firewall: &Firewall, family: &str, protocol: &str, port: u16) -> Reachability {
+    let mut chains: Vec<&FilterChain> = firewall
+        .chains
+        .iter()
+        .filter(|chain| chain.input && (chain.family == family || chain.family == "inet"))
+        .collect();
+    chains.sort_by_key(|chain| chain.priority);
+    let mut result = Reachability::Open;
+    for chain in chains {
+        let (mut accepted, mut dropped) = (vec![], vec![]);
+        let outcome = match evaluate_chain(
+            firewall,
+            chain,
+            protocol,
+            port,
+            &mut accepted,
+            &mut dropped,
+            0,
+        ) {
+            Outcome::Continue => match chain.policy {
+                Some(Verdict::Drop) => Outcome::Drop,
+                _ => Outcome::Accept,
+            },
+            outcome => outcome,
+        };
+        let reachability = match outcome {
+            Outcome::Accept if dropped.is_empty() => Reachability::Open,
+            // any source but the dropped ones
+            Outcome::Accept => {
+                dropped.dedup();
+                Reachability::Restricted(
+                    dropped
+                        .iter()
+                        .map(|source| format!("!{}", source))
+                        .collect(),
+                )
+            }
+            _ if accepted.is_empty() => Reachability::Firewalled,
+            _ => {
+                accepted.dedup();
+                Reachability::Restricted(accepted)
+            }
+        };
+        result = combine(result, reachability);
+    }
+    result
+}
+
+/// Reachability of a listening address, e.g. `0.0.0.0:5432/tcp` - none for loopback addresses.
+pub fn evaluate(firewall: &Firewall, address: &str) -> Option<Reachability> {
+    let (bind, protocol) = address.rsplit_once('/')?;
+    let (ip, port) = bind.rsplit_once(':')?;
+    let port: u16 = port.parse().ok()?;
+    let ip = ip.trim_start_matches('[').trim_end_matches(']');
+    let ip = ip.split('%').next().unwrap_or_default();
+    if ip.starts_with("127.") || ip == "::1" {
+        return None;
+    }
+    let protocol = protocol.trim_end_matches('6');
+    let families: &[&str] = match ip {
+        // dual stack sockets accept both families
+        "*" | "::" => &["ip", "ip6"],
+        ip if ip.contains(':') => &["ip6"],
+        _ => &["ip"],
+    };
+    families
+        .iter()
+        .map(|family| evaluate_family(firewall, family, protocol, port))
+        .max_by_key(|reachability| match reachability {
+            Reachability::Firewalled => 0,
+            Reachability::Restricted(_) => 1,
+            Reachability::Open => 2,
+        })
+}
+
+/// Evaluate the listening addresses of all processes.
+pub fn annotate(firewall: &Option<Firewall>, processes: &mut [Process]) {
+    if let Some(firewall) = firewall {
+        for process in processes {
+            for address in &process.addresses {
+                if let Some(reachability) = evaluate(firewall, address) {
+                    process
+                        .reachability
+                        .insert(address.to_string(), reachability);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `nft -j list ruleset` of an inet table - policy drop, a service chain and an interface
+    /// condition the evaluation does not model.
+    const NFTABLES: &str = r#"{"nftables": [
+{"metainfo": {"version": "1.0.6", "release_name": "Lester Gooch #5", "json_schema_version": 1}},
+{"table": {"family": "inet", "name": "filter", "handle": 1}},
+{"chain": {"family": "inet", "table": "filter", "name": "input", "handle": 1, "type": "filter", "hook": "input", "prio": 0, "policy": "drop"}},
+{"chain": {"family": "inet", "table": "filter", "name": "services", "handle": 2}},
+{"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 3, "expr": [
+  {"match": {"op": "in", "left": {"ct": {"key": "state"}}, "right": ["established", "related"]}},
+  {"accept": null}]}},
+{"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 4, "expr": [
+  {"match": {"op": "==", "left": {"meta": {"key": "iifname"}}, "right": "lo"}},
+  {"accept": null}]}},
+{"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 5, "expr": [
+  {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 22}},
+  {"match": {"op": "in", "left": {"ct": {"key": "state"}}, "right": "new"}},
+  {"counter": {"packets": 0, "bytes": 0}},
+  {"accept": null}]}},
+{"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 6, "expr": [
+  {"match": {"op": "==", "left": {"payload": {"protocol": "ip", "field": "saddr"}}, "right": {"prefix": {"addr": "10.0.0.0", "len": 8}}}},
+  {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 5432}},
+  {"accept": null}]}},
+{"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 7, "expr": [
+  {"jump": {"target": "services"}}]}},
+{"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 8, "expr": [
+  {"match": {"op": "==", "left": {"meta": {"key": "iifname"}}, "right": "eth1"}},
+  {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 8080}},
+  {"accept": null}]}},
+{"rule": {"family": "inet", "table": "filter", "chain": "services", "handle": 9, "expr": [
+  {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": {"set": [80, 443]}}},
+  {"accept": null}]}}
+]}"#;
+
+    /// `nft -j list ruleset` without any tables.
+    const EMPTY: &str = r#"{"nftables": [{"metainfo": {"version": "1.0.6", "release_name": "Lester Gooch #5", "json_schema_version": 1}}]}"#;
+
+    const IPTABLES: &str = "\
+# Generated by iptables-save v1.8.7 on Mon Jan  1 00:00:00 2024
+*filter
+:INPUT DROP [0:0]
+:FORWARD ACCEPT [0:0]
+:OUTPUT ACCEPT [0:0]
+:WEB - [0:0]
+-A INPUT -i lo -j ACCEPT
+-A INPUT -m conntrack --ctstate RELATED,ESTABLISHED -j ACCEPT
+-A INPUT -p tcp -m tcp --dport 22 -m conntrack --ctstate NEW -m comment --comment \"ssh from anywhere\" -j ACCEPT
+-A INPUT -s 192.168.1.0/24 -p tcp -m multiport --dports 5432,6379 -j ACCEPT
+-A INPUT -p tcp -m addrtype --dst-type LOCAL -m tcp --dport 8080 -j ACCEPT
+-A INPUT -p tcp -g WEB
+-A INPUT -p tcp -m tcp --dport 9090 -j ACCEPT
+-A WEB -p tcp -m tcp --dport 80 -j ACCEPT
+COMMIT
+";
+
+    const IP6TABLES: &str = "\
+*filter
+:INPUT ACCEPT [0:0]
+:FORWARD ACCEPT [0:0]
+:OUTPUT ACCEPT [0:0]
+-A INPUT -p tcp -m tcp --dport 5432 -j REJECT --reject-with icmp6-port-unreachable
+COMMIT
+";
+
+    /// Drops for some sources before an accept for all.
+    const BLOCKLIST: &str = "\
+*filter
+:INPUT ACCEPT [0:0]
+-A INPUT -s 10.0.5.0/24 -p tcp -m tcp --dport 22 -j ACCEPT
+-A INPUT -s 10.0.0.0/8 -p tcp -m tcp --dport 22 -j DROP
+-A INPUT -s 10.0.5.0/24 -p tcp -m tcp --dport 22 -j DROP
+-A INPUT -p tcp -m tcp --dport 22 -j ACCEPT
+-A INPUT -s 192.168.0.0/16 -p tcp -m tcp --dport 3306 -j REJECT
+COMMIT
+";
+
+    fn sections(nftables: &str, iptables: &str, ip6tables: &str) -> HashMap<String, String> {
+        HashMap::from([
+            (String::from("nftables"), nftables.to_string()),
+            (String::from("iptables"), iptables.to_string()),
+            (String::from("ip6tables"), ip6tables.to_string()),
+        ])
+    }
+
+    fn restricted(sources: &[&str]) -> Option<Reachability> {
+        Some(Reachability::Restricted(
+            sources.iter().map(|s| s.to_string()).collect(),
+        ))
+    }
+
+    #[test]
+    fn parses_nftables() {
+        let firewall = parse_nftables(NFTABLES).unwrap();
+        assert_eq!(firewall.chains.len(), 2);
+        let input = &firewall.chains[0];
+        assert!(input.input);
+        assert_eq!(input.policy, Some(Verdict::Drop));
+        // established and loopback rules never decide for new connections, eth1 is not modelled
+        assert_eq!(input.rules.len(), 3);
+        assert_eq!(input.rules[0].ports, vec![(22, 22)]);
+        assert_eq!(input.rules[1].sources, vec!["10.0.0.0/8"]);
+        assert_eq!(
+            input.rules[2].verdict,
+            Verdict::Jump(String::from("services"))
+        );
+        assert_eq!(
+            firewall.chains[1].rules[0].ports,
+            vec![(80, 80), (443, 443)]
+        );
+    }
+
+    #[test]
+    fn evaluates_nftables() {
+        let firewall = parse_nftables(NFTABLES).unwrap();
+        let evaluate = |address| evaluate(&firewall, address);
+        assert_eq!(evaluate("0.0.0.0:22/tcp"), Some(Reachability::Open));
+        assert_eq!(evaluate("0.0.0.0:5432/tcp"), restricted(&["10.0.0.0/8"]));
+        assert_eq!(evaluate("[::]:443/tcp6"), Some(Reachability::Open));
+        assert_eq!(evaluate("0.0.0.0:8080/tcp"), Some(Reachability::Firewalled));
+        assert_eq!(evaluate("0.0.0.0:22/udp"), Some(Reachability::Firewalled));
+        assert_eq!(evaluate("127.0.0.1:5432/tcp"), None);
+    }
+
+    #[test]
+    fn parses_iptables() {
+        let chains = parse_iptables(IPTABLES, "ip");
+        assert_eq!(chains.len(), 4);
+        let input = &chains[0];
+        assert_eq!(input.policy, Some(Verdict::Drop));
+        assert_eq!(chains[3].policy, None);
+        // loopback, established and the addrtype rule are skipped
+        assert_eq!(input.rules.len(), 4);
+        assert_eq!(input.rules[0].ports, vec![(22, 22)]);
+        assert_eq!(input.rules[1].sources, vec!["192.168.1.0/24"]);
+        assert_eq!(input.rules[1].ports, vec![(5432, 5432), (6379, 6379)]);
+        assert_eq!(input.rules[2].verdict, Verdict::Goto(String::from("WEB")));
+    }
+
+    #[test]
+    fn evaluates_iptables_dual_stack() {
+        let firewall = parse_sections(&sections("", IPTABLES, IP6TABLES)).unwrap();
+        assert_eq!(firewall.source, "iptables");
+        let evaluate = |address| evaluate(&firewall, address);
+        assert_eq!(evaluate("0.0.0.0:22/tcp"), Some(Reachability::Open));
+        assert_eq!(evaluate("0.0.0.0:80/tcp"), Some(Reachability::Open));
+        // WEB was entered by goto - its end does not continue in INPUT
+        assert_eq!(evaluate("0.0.0.0:9090/tcp"), Some(Reachability::Firewalled));
+        assert_eq!(evaluate("0.0.0.0:8080/tcp"), Some(Reachability::Firewalled));
+        assert_eq!(
+            evaluate("[2001:db8::1]:8080/tcp6"),
+            Some(Reachability::Open)
+        );
+        // the most open family of dual stack sockets
+        assert_eq!(evaluate("[::]:5432/tcp6"), restricted(&["192.168.1.0/24"]));
+        assert_eq!(evaluate("*:8080/tcp"), Some(Reachability::Open));
+    }
+
+    #[test]
+    fn falls_back_to_iptables() {
+        let firewall = parse_sections(&sections(EMPTY, IPTABLES, "")).unwrap();
+        assert_eq!(firewall.source, "iptables");
+        let firewall = parse_sections(&sections(NFTABLES, IPTABLES, "")).unwrap();
+        assert_eq!(firewall.source, "nftables");
+        // nft without tables and no iptables rules - nothing filters
+        let firewall = parse_sections(&sections(EMPTY, "", "")).unwrap();
+        assert!(firewall.chains.is_empty());
+        assert_eq!(
+            evaluate(&firewall, "0.0.0.0:22/tcp"),
+            Some(Reachability::Open)
+        );
+        // no permission to read the rules
+        assert_eq!(parse_sections(&sections("", "", "")), None);
+    }
+
+    #[test]
+    fn restricts_dropped_sources() {
+        let firewall = parse_sections(&sections("", BLOCKLIST, "")).unwrap();
+        let evaluate = |address| evaluate(&firewall, address);
+        // 10.0.5.0/24 was accepted before its drop
+        assert_eq!(evaluate("0.0.0.0:22/tcp"), restricted(&["!10.0.0.0/8"]));
+        // the policy accepts the others
+        assert_eq!(
+            evaluate("0.0.0.0:3306/tcp"),
+            restricted(&["!192.168.0.0/16"])
+        );
+        assert_eq!(evaluate("0.0.0.0:80/tcp"), Some(Reachability::Open));
+        let sources = |sources: &[&str]| sources.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            Some(combine(
+                Reachability::Restricted(sources(&["10.0.0.0/8"])),
+                Reachability::Restricted(sources(&["!10.9.0.0/16"]))
+            )),
+            restricted(&["10.0.0.0/8", "!10.9.0.0/16"])
+        );
+        assert_eq!(
+            combine(
+                Reachability::Restricted(sources(&["10.0.0.0/8"])),
+                Reachability::Restricted(sources(&["192.168.0.0/16"]))
+            ),
+            Reachability::Firewalled
+        );
+    }
+}
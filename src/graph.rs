@@ -1,7 +1,72 @@
-use crate::model::{Connection, Interface, Machine, Model, Process};
+use crate::model::{
+    Connection, Container, Interface, Machine, Model, Namespace, Process, Reachability, SocketUser,
+    Status, UnixSocket,
+};
+use crate::neighbor::is_reachable;
+use crate::netns::ROOT;
 use dot_writer::{Attributes, Color, DotWriter, Scope, Shape, Style};
+use ipaddress::IPAddress;
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// How process nodes are aggregated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    /// one node per process name
+    Process,
+    /// one node per systemd unit - processes without unit are grouped by name
+    Unit,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "process" => Ok(GroupBy::Process),
+            "unit" => Ok(GroupBy::Unit),
+            _ => Err(format!(
+                "invalid grouping '{}' - expected process or unit",
+                input
+            )),
+        }
+    }
+}
+
+/// Which listeners are drawn - by the reachability through the firewall.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Listeners {
+    All,
+    /// not firewalled - listeners of hosts without readable rules included
+    Reachable,
+    Firewalled,
+}
+
+impl FromStr for Listeners {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "all" => Ok(Listeners::All),
+            "reachable" => Ok(Listeners::Reachable),
+            "firewalled" => Ok(Listeners::Firewalled),
+            _ => Err(format!(
+                "invalid listener filter '{}' - expected all, reachable or firewalled",
+                input
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphOptions {
+    pub group_by: GroupBy,
+    pub listeners: Listeners,
+    /// draw the IPC edges of unix sockets
+    pub ipc: bool,
+}
 
 fn ip_only(input: &String) -> String {
     lazy_static! {
@@ -18,12 +83,673 @@ fn ip_only(input: &String) -> String {
     result = RE4.replace(&result, "").to_string();
     result
 }
-fn sanitiza_label(input: &String) -> String {
-    let output = input.replace("-", "").replace("@", "").replace(":", "_");
-    output
+fn sanitiza_label(input: &str) -> String {
+    input.replace("-", "").replace("@", "").replace(":", "_")
+}
+
+/// Part of an unquoted DOT node ID - process names, units, host and interface names may contain
+/// dots, slashes or spaces.
+fn node_id(input: &str) -> String {
+    sanitiza_label(input)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Format seconds since the epoch as UTC date and time.
+fn format_time(secs: u64) -> String {
+    // civil from days - http://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+fn escape_quoted(input: &str) -> String {
+    input.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// One paragraph per process: pid, parent, owner and start time, executable and command line.
+fn process_tooltip(processes: &[&Process]) -> String {
+    let mut paragraphs = vec![];
+    for process in processes {
+        let mut lines = vec![];
+        let mut header = match process.pid {
+            Some(pid) => format!("pid {}", pid),
+            None => String::from("pid unknown"),
+        };
+        if let Some(ppid) = process.ppid {
+            header.push_str(&format!(" ppid {}", ppid));
+        }
+        match (&process.user, process.uid) {
+            (Some(user), _) => header.push_str(&format!(" user {}", user)),
+            (None, Some(uid)) => header.push_str(&format!(" uid {}", uid)),
+            _ => {}
+        }
+        if let Some(start_time) = process.start_time {
+            header.push_str(&format!(" started {}", format_time(start_time)));
+        }
+        lines.push(header);
+        if let Some(unit) = &process.unit {
+            lines.push(format!("unit {}", unit));
+        }
+        if let Some(exe) = &process.exe {
+            lines.push(exe.to_string());
+        }
+        if let Some(cmdline) = &process.cmdline {
+            lines.push(cmdline.to_string());
+        }
+        // `\n` is the DOT line break inside quoted strings
+        paragraphs.push(escape_quoted(&lines.join("\n")).replace('\n', "\\n"));
+    }
+    paragraphs.join("\\n\\n")
+}
+
+/// State, kind, hardware address and MTU of an interface.
+fn interface_tooltip(interface: &Interface) -> String {
+    let mut fields = vec![];
+    if let Some(state) = &interface.state {
+        fields.push(state.to_string());
+    }
+    if let Some(kind) = interface.kind.as_ref().or(interface.link_type.as_ref()) {
+        fields.push(kind.to_string());
+    }
+    if let Some(mac) = &interface.mac {
+        fields.push(mac.to_string());
+    }
+    if let Some(mtu) = interface.mtu {
+        fields.push(format!("mtu {}", mtu));
+    }
+    escape_quoted(&fields.join(" "))
+}
+
+/// Label of the node of a process - its name or its unit.
+fn node_label(name: &str, unit: &Option<String>, options: &GraphOptions) -> String {
+    let label = match (options.group_by, unit) {
+        (GroupBy::Unit, Some(unit)) => String::from(unit),
+        _ => String::from(name),
+    };
+    if label.is_empty() {
+        return String::from("_unknown_");
+    }
+    label
+}
+
+/// Processes are collected per pid - group them by name or unit into one node each.
+fn group_processes<'a>(
+    processes: &[&'a Process],
+    options: &GraphOptions,
+) -> Vec<(String, Vec<&'a Process>)> {
+    let mut groups: Vec<(String, Vec<&Process>)> = vec![];
+    for process in processes {
+        let label = node_label(&process.name, &process.unit, options);
+        match groups.iter_mut().find(|(name, _)| *name == label) {
+            Some((_, group)) => group.push(process),
+            None => groups.push((label, vec![process])),
+        }
+    }
+    groups
+}
+
+/// Split processes by container - processes outside of containers come first.
+fn group_containers(processes: &[Process]) -> Vec<(Option<&Container>, Vec<&Process>)> {
+    let mut groups: Vec<(Option<&Container>, Vec<&Process>)> = vec![(None, vec![])];
+    for process in processes {
+        let container = process.container.as_ref();
+        match groups
+            .iter_mut()
+            .find(|(other, _)| other.map(|c| &c.id) == container.map(|c| &c.id))
+        {
+            Some((_, group)) => group.push(process),
+            None => groups.push((container, vec![process])),
+        }
+    }
+    groups
+}
+
+fn container_label(container: &Container) -> String {
+    let mut label = match &container.name {
+        Some(name) => name.to_string(),
+        None => container.id.chars().take(12).collect(),
+    };
+    if let Some(image) = &container.image {
+        label.push_str(&format!(" ({})", image));
+    }
+    label
+}
+
+/// Node name prefix of the processes of a container.
+fn container_prefix(prefix: &str, container: &Container) -> String {
+    format!(
+        "{}c{}_",
+        prefix,
+        container.id.chars().take(12).collect::<String>()
+    )
+}
+
+/// Node name and label of the process of a socket - named like the process nodes.
+fn user_node(
+    prefix: &str,
+    user: &SocketUser,
+    namespace: &Namespace,
+    options: &GraphOptions,
+) -> (String, String) {
+    let label = node_label(&user.name, &user.unit, options);
+    let container = namespace
+        .processes
+        .iter()
+        .find(|process| user.pid.is_some() && process.pid == user.pid)
+        .and_then(|process| process.container.as_ref());
+    let prefix = match container {
+        Some(container) => container_prefix(prefix, container),
+        None => prefix.to_string(),
+    };
+    (format!("{}{}", prefix, node_id(&label)), label)
+}
+
+/// Node of the process of a socket - drawn unless drawn before.
+fn draw_user(
+    cluster: &mut Scope,
+    prefix: &str,
+    user: &SocketUser,
+    namespace: &Namespace,
+    options: &GraphOptions,
+    drawn: &mut Vec<String>,
+) -> String {
+    let (name, label) = user_node(prefix, user, namespace, options);
+    if !drawn.contains(&name) {
+        cluster
+            .node_named(&name)
+            .set_label(&label)
+            .set_shape(Shape::Circle);
+        drawn.push(name.to_string());
+    }
+    name
+}
+
+/// The process of a connection - the first user or a process known by name only.
+fn connection_user(connection: &Connection) -> SocketUser {
+    connection
+        .users
+        .first()
+        .cloned()
+        .unwrap_or_else(|| SocketUser {
+            name: connection.process.to_string(),
+            pid: None,
+            fd: None,
+            unit: None,
+        })
+}
+
+/// Process listening on `addr`:`port` - directly or on all addresses.
+fn find_listener<'a>(namespace: &'a Namespace, addr: &str, port: &str) -> Option<&'a Process> {
+    namespace.processes.iter().find(|process| {
+        process.addresses.iter().any(|address| {
+            let bind = address
+                .rsplit_once('/')
+                .map_or(address.as_str(), |(bind, _)| bind);
+            match bind.rsplit_once(':') {
+                Some((ip, other)) => {
+                    other == port && (ip == addr || ["*", "0.0.0.0", "[::]"].contains(&ip))
+                }
+                None => false,
+            }
+        })
+    })
+}
+
+/// The connection was accepted by a listener of its namespace.
+fn is_server_side(namespace: &Namespace, connection: &Connection) -> bool {
+    find_listener(namespace, &connection.local_addr, &connection.local_port).is_some()
+}
+
+/// IPC edges from the clients of unix sockets to the processes listening on the path - labeled
+/// with the paths. Processes without other sockets get a node of their own.
+fn generate_ipc(
+    cluster: &mut Scope,
+    prefix: &str,
+    namespace: &Namespace,
+    options: &GraphOptions,
+    drawn: &mut Vec<String>,
+) {
+    let sockets = &namespace.unix_sockets;
+    let listening: HashSet<&String> = sockets
+        .iter()
+        .filter(|socket| socket.state == "LISTEN")
+        .filter_map(|socket| socket.path.as_ref())
+        .collect();
+    let by_inode: HashMap<u64, &UnixSocket> = sockets
+        .iter()
+        .map(|socket| (socket.inode, socket))
+        .collect();
+    let mut edges: Vec<(String, String, Vec<&String>)> = vec![];
+    for server in sockets.iter().filter(|socket| socket.state != "LISTEN") {
+        let path = match &server.path {
+            Some(path) if listening.contains(&path) => path,
+            _ => continue,
+        };
+        let client = match server.peer.and_then(|peer| by_inode.get(&peer)) {
+            Some(client) => client,
+            None => continue,
+        };
+        let (from, to) = match (client.users.first(), server.users.first()) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        let from = draw_user(cluster, prefix, from, namespace, options, drawn);
+        let to = draw_user(cluster, prefix, to, namespace, options, drawn);
+        if from == to {
+            continue;
+        }
+        match edges
+            .iter_mut()
+            .find(|(other_from, other_to, _)| *other_from == from && *other_to == to)
+        {
+            Some((_, _, paths)) => {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+            None => edges.push((from, to, vec![path])),
+        }
+    }
+    for (from, to, paths) in edges {
+        let paths: Vec<String> = paths.iter().map(|path| escape_quoted(path)).collect();
+        cluster
+            .edge(from, to)
+            .attributes()
+            .set_label(&paths.join("\\n"))
+            .set("color", "purple", false);
+    }
+}
+
+/// Process nodes of one scope - the bind edges are returned to be written by the namespace
+/// scope, an edge written inside a container cluster would pull the interface into it.
+///
+/// Processes without listeners left by the listener filter are not drawn.
+fn generate_processes(
+    cluster: &mut Scope,
+    prefix: &str,
+    namespace_prefix: &str,
+    processes: &[&Process],
+    namespace: &Namespace,
+    options: &GraphOptions,
+    drawn: &mut Vec<String>,
+) -> Vec<(String, String, Option<Reachability>)> {
+    let mut edges = vec![];
+    for (label, processes) in group_processes(processes, options) {
+        let mut name = String::from(prefix);
+        name.push_str(&node_id(&label));
+        let mut binds: Vec<(&String, Option<&Reachability>)> = vec![];
+        for process in &processes {
+            for bind in &process.addresses {
+                if !binds.iter().any(|(other, _)| *other == bind) {
+                    binds.push((bind, process.reachability.get(bind)));
+                }
+            }
+        }
+        binds.retain(|(_, reachability)| match options.listeners {
+            Listeners::All => true,
+            Listeners::Reachable => *reachability != Some(&Reachability::Firewalled),
+            Listeners::Firewalled => *reachability == Some(&Reachability::Firewalled),
+        });
+        if binds.is_empty() && options.listeners != Listeners::All {
+            continue;
+        }
+        cluster
+            .node_named(&name)
+            .set_label(&label)
+            .set("tooltip", &process_tooltip(&processes), true)
+            .set_shape(Shape::Circle);
+        drawn.push(name.to_string());
+        for (bind, reachability) in binds {
+            let bindport = ip_only(bind);
+            if bindport.eq("127.0.0.1") || bindport.eq("[::1]") {
+                // TODO: treat local binds
+                continue;
+            }
+            // `*` in models written before the addresses were normalized
+            if bindport.eq("*") || bindport.eq("0.0.0.0") || bindport.eq("[::]") {
+                for interface in &namespace.interfaces {
+                    // TODO: check IPv4/6 and add clap options
+                    for addr in &interface.addresses {
+                        edges.push((
+                            name.to_string(),
+                            ip_only(&format!(
+                                "{}{}:\"{}\"",
+                                namespace_prefix,
+                                node_id(&ip_only(&interface.name)),
+                                sanitiza_label(&ip_only(addr))
+                            )),
+                            reachability.cloned(),
+                        ));
+                    }
+                }
+            } else {
+                edges.push((
+                    name.to_string(),
+                    ip_only(&format!(
+                        "{}{}:\"{}\"",
+                        namespace_prefix,
+                        "TODO",
+                        sanitiza_label(&ip_only(&bindport))
+                    )),
+                    reachability.cloned(),
+                ));
+            }
+        }
+    }
+    edges
+}
+
+/// Interfaces and processes of a namespace - the names of the process nodes are returned for
+/// the connection edges.
+fn generate_namespace(
+    cluster: &mut Scope,
+    prefix: &str,
+    namespace: &Namespace,
+    connections: &[&Connection],
+    options: &GraphOptions,
+) -> Vec<String> {
+    for interface in &namespace.interfaces {
+        let mut device = String::from(prefix);
+        device.push_str(&node_id(&interface.name));
+        let mut label = format!("<{}> {}", ip_only(&device), interface.name);
+        for addr in &interface.addresses {
+            label.push_str(" | ");
+            label.push_str(&format!("<{}> {}", sanitiza_label(&ip_only(addr)), &addr));
+        }
+        let mut node = cluster.node_named(device);
+        node.set_label(&label).set_shape(Shape::Record);
+        let tooltip = interface_tooltip(interface);
+        if !tooltip.is_empty() {
+            node.set("tooltip", &tooltip, true);
+        }
+        if interface.is_down() {
+            node.set_color(Color::LightGrey).set_font_color(Color::Grey);
+        }
+    }
+    let mut edges = vec![];
+    let mut drawn = vec![];
+    for (container, processes) in group_containers(&namespace.processes) {
+        match container {
+            None => edges.extend(generate_processes(
+                cluster, prefix, prefix, &processes, namespace, options, &mut drawn,
+            )),
+            Some(container) => {
+                let mut nested = cluster.cluster();
+                nested.set_style(Style::Filled);
+                nested.set_color(Color::PaleTurquoise);
+                nested.set_label(&container_label(container));
+                edges.extend(generate_processes(
+                    &mut nested,
+                    &container_prefix(prefix, container),
+                    prefix,
+                    &processes,
+                    namespace,
+                    options,
+                    &mut drawn,
+                ));
+            }
+        }
+    }
+    if options.ipc {
+        generate_ipc(cluster, prefix, namespace, options, &mut drawn);
+    }
+    // clients without listener
+    for connection in connections {
+        if !is_server_side(namespace, connection) {
+            let user = connection_user(connection);
+            draw_user(cluster, prefix, &user, namespace, options, &mut drawn);
+        }
+    }
+    for (from, to, reachability) in edges {
+        let edge = cluster.edge(from, to);
+        match reachability {
+            None | Some(Reachability::Open) => {}
+            Some(Reachability::Restricted(sources)) => {
+                let (excluded, allowed): (Vec<&str>, Vec<&str>) = sources
+                    .iter()
+                    .map(String::as_str)
+                    .partition(|source| source.starts_with('!'));
+                let mut tooltip = match allowed.is_empty() {
+                    true => String::from("from any"),
+                    false => format!("from {}", allowed.join(", ")),
+                };
+                if !excluded.is_empty() {
+                    let excluded: Vec<&str> = excluded.iter().map(|s| &s[1..]).collect();
+                    tooltip.push_str(&format!(" except {}", excluded.join(", ")));
+                }
+                edge.attributes()
+                    .set_style(Style::Dashed)
+                    .set("color", "orange", false)
+                    .set("tooltip", &tooltip, true);
+            }
+            Some(Reachability::Firewalled) => {
+                edge.attributes()
+                    .set_style(Style::Dashed)
+                    .set_color(Color::Red)
+                    .set("tooltip", "firewalled", true);
+            }
+        }
+    }
+    drawn
+}
+
+/// Node name prefix of a namespace - nodes of other namespaces must not clash with the root one.
+fn namespace_prefix(machine: &Machine, namespace: &Namespace) -> String {
+    let mut prefix = node_id(&machine.hostname);
+    // IDs must not start with a digit - hosts known by their address only
+    if prefix.starts_with(|c: char| c.is_ascii_digit()) {
+        prefix.insert(0, '_');
+    }
+    if namespace.name != ROOT {
+        prefix.push('_');
+        prefix.extend(
+            namespace
+                .name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }),
+        );
+        prefix.push('_');
+    }
+    prefix
+}
+
+/// Interfaces of all namespaces of a machine with their node names.
+fn machine_interfaces(machine: &Machine) -> Vec<(&Namespace, &Interface, String)> {
+    let mut interfaces = vec![];
+    for namespace in &machine.namespaces {
+        let prefix = namespace_prefix(machine, namespace);
+        for interface in &namespace.interfaces {
+            let node = format!("{}{}", prefix, node_id(&interface.name));
+            interfaces.push((namespace, interface, node));
+        }
+    }
+    interfaces
+}
+
+/// The parent or peer of an interface in another namespace.
+///
+/// ifindexes of other namespaces may be the same, so only a peer pointing back is taken. If that
+/// is ambiguous - or the parent does not point back, like the host side of a macvlan - the
+/// namespace is taken from another peer with the same `link_netnsid`, which names one namespace.
+fn find_remote_link<'a>(
+    interfaces: &'a [(&Namespace, &Interface, String)],
+    namespace: &Namespace,
+    interface: &Interface,
+) -> Option<&'a (&'a Namespace, &'a Interface, String)> {
+    let remote = |interface: &Interface| -> Vec<&'a (&'a Namespace, &'a Interface, String)> {
+        interfaces
+            .iter()
+            .filter(|(ns, other, _)| {
+                ns.name != namespace.name
+                    && interface.link_index.is_some()
+                    && other.index == interface.link_index
+            })
+            .collect()
+    };
+    let points_back = |interface: &Interface| {
+        let mut candidates = remote(interface);
+        candidates.retain(|(_, other, _)| {
+            other.link_index.is_some() && other.link_index == interface.index
+        });
+        match candidates.len() {
+            1 => candidates.first().copied(),
+            _ => None,
+        }
+    };
+    if let Some(peer) = points_back(interface) {
+        return Some(peer);
+    }
+    let netnsid = interface.link_netnsid?;
+    let mut namespaces: Vec<&String> = interfaces
+        .iter()
+        .filter(|(ns, other, _)| {
+            ns.name == namespace.name
+                && other.name != interface.name
+                && other.link_netnsid == Some(netnsid)
+        })
+        .filter_map(|(_, other, _)| points_back(other))
+        .map(|(ns, _, _)| &ns.name)
+        .collect();
+    namespaces.sort();
+    namespaces.dedup();
+    match namespaces[..] {
+        [name] => {
+            let candidates: Vec<_> = remote(interface)
+                .into_iter()
+                .filter(|(ns, _, _)| &ns.name == name)
+                .collect();
+            match candidates.len() {
+                1 => candidates.first().copied(),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Layer 2 topology inside a machine: bridge and bond ports to their master, vlans to their
+/// parent and veth pairs - also across namespaces - as undirected edges.
+fn generate_links(cluster: &mut Scope, machine: &Machine) {
+    let interfaces = machine_interfaces(machine);
+    let find = |namespace: &Namespace, name: &str| {
+        interfaces
+            .iter()
+            .find(|(ns, interface, _)| ns.name == namespace.name && interface.name == name)
+    };
+    let mut peers: Vec<(&String, &String)> = vec![];
+    for (namespace, interface, node) in &interfaces {
+        if let Some((_, master, to)) = interface
+            .master
+            .as_ref()
+            .and_then(|master| find(namespace, master))
+        {
+            cluster
+                .edge(node, to)
+                .attributes()
+                .set_label(master.kind.as_deref().unwrap_or("master"));
+        }
+        let parent = match &interface.link {
+            Some(link) => find(namespace, link),
+            None => find_remote_link(&interfaces, namespace, interface),
+        };
+        let (_, parent, to) = match parent {
+            Some(parent) => parent,
+            None => continue,
+        };
+        // a peer points back - the brief format has no kinds
+        let is_peer = interface.kind.as_deref() == Some("veth")
+            || parent.link.as_ref() == Some(&interface.name)
+            || (interface.index.is_some() && parent.link_index == interface.index);
+        if is_peer {
+            if peers.contains(&(to, node)) {
+                continue;
+            }
+            peers.push((node, to));
+            cluster
+                .edge(node, to)
+                .attributes()
+                .set_label("veth")
+                .set("dir", "none", false);
+        } else {
+            cluster
+                .edge(node, to)
+                .attributes()
+                .set_label(interface.kind.as_deref().unwrap_or("link"))
+                .set_style(Style::Dashed);
+        }
+    }
 }
 
-fn generate_machine_node(digraph: &mut Scope, machine: &Machine) {
+/// Failed machines are marked in red, the errors are shown as tooltip.
+fn generate_status(cluster: &mut Scope, machine: &Machine) {
+    let mut label = sanitiza_label(&machine.hostname);
+    let errors: Vec<String> = machine.errors.iter().map(|e| escape_quoted(e)).collect();
+    // `\n` is the DOT line break inside quoted strings
+    let errors = errors.join("\\n");
+    let mut tooltip = errors.to_string();
+    if machine.unattributed > 0 {
+        if !tooltip.is_empty() {
+            tooltip.push_str("\\n");
+        }
+        tooltip.push_str(&format!("{} sockets without process", machine.unattributed));
+    }
+    match machine.status {
+        Status::Ok => {}
+        Status::Partial => {
+            label.push_str(" (incomplete)");
+            cluster.set_font_color(Color::Red);
+        }
+        Status::Unreachable => {
+            label.push_str(" (unreachable)");
+            cluster.set_style(Style::Dashed);
+            cluster.set_color(Color::Red);
+            cluster.set_font_color(Color::Red);
+            // an empty cluster is not drawn
+            cluster
+                .node_named(format!("\"{}_status\"", sanitiza_label(&machine.hostname)))
+                .set_label(&errors)
+                .set("shape", "note", false)
+                .set_color(Color::Red);
+        }
+    }
+    cluster.set_label(&label);
+    if !tooltip.is_empty() {
+        cluster.set("tooltip", &tooltip, true);
+    }
+}
+
+/// The machine a connection was collected on.
+fn is_connection_of(machine: &Machine, connection: &Connection) -> bool {
+    match &machine.address {
+        Some(address) => *address == connection.host,
+        None => machine.hostname == connection.host,
+    }
+}
+
+fn generate_machine_node(
+    digraph: &mut Scope,
+    machine: &Machine,
+    connections: &[Connection],
+    options: &GraphOptions,
+) -> Vec<String> {
+    let mut drawn = vec![];
     {
         let mut cluster = digraph.cluster();
         cluster.set_style(Style::Filled);
@@ -33,78 +759,618 @@ fn generate_machine_node(digraph: &mut Scope, machine: &Machine) {
             .node_attributes()
             .set_style(Style::Filled)
             .set_color(Color::White);
-        cluster.set_label(&sanitiza_label(&machine.hostname));
-        for interface in &machine.interfaces {
-            let mut device = String::from(&sanitiza_label(&machine.hostname));
-            device.push_str(&sanitiza_label(&interface.name));
-            let mut label = String::from(format!("<{}> {}", ip_only(&device), interface.name));
-            for addr in &interface.addresses {
-                label.push_str(" | ");
-                label.push_str(&format!("<{}> {}", sanitiza_label(&ip_only(&addr)), &addr));
+        generate_status(&mut cluster, machine);
+        for namespace in &machine.namespaces {
+            let prefix = namespace_prefix(machine, namespace);
+            let connections: Vec<&Connection> = connections
+                .iter()
+                .filter(|c| is_connection_of(machine, c) && c.namespace == namespace.name)
+                .collect();
+            if namespace.name == ROOT {
+                drawn.extend(generate_namespace(
+                    &mut cluster,
+                    &prefix,
+                    namespace,
+                    &connections,
+                    options,
+                ));
+            } else {
+                let mut nested = cluster.cluster();
+                nested.set_style(Style::Filled);
+                nested.set_color(Color::Grey);
+                nested.set_label(&namespace.name);
+                drawn.extend(generate_namespace(
+                    &mut nested,
+                    &prefix,
+                    namespace,
+                    &connections,
+                    options,
+                ));
             }
-            cluster
-                .node_named(device)
-                .set_label(&label)
-                .set_shape(Shape::Record);
         }
-        for process in &machine.processes {
-            let mut label = String::from(&process.name);
-            let mut name = String::from(&machine.hostname);
-            if label.eq("") {
-                label = String::from("_unknown_");
+        generate_links(&mut cluster, machine);
+    }
+    drawn
+}
+
+/// An interface address of the model and its record port.
+struct Port<'a> {
+    machine: &'a Machine,
+    namespace: &'a Namespace,
+    /// with prefix length, e.g. `10.0.0.1/24`
+    address: &'a String,
+    port: String,
+}
+
+/// All interface addresses of the model.
+fn interface_ports(model: &Model) -> Vec<Port<'_>> {
+    let mut ports = vec![];
+    for machine in &model.machines {
+        for namespace in &machine.namespaces {
+            let prefix = namespace_prefix(machine, namespace);
+            for interface in &namespace.interfaces {
+                for address in &interface.addresses {
+                    ports.push(Port {
+                        machine,
+                        namespace,
+                        address,
+                        port: format!(
+                            "{}{}:\"{}\"",
+                            prefix,
+                            node_id(&interface.name),
+                            sanitiza_label(&ip_only(address))
+                        ),
+                    });
+                }
             }
-            name.push_str(&label);
-            cluster
-                .node_named(&name)
-                .set_label(&label)
-                .set_shape(Shape::Circle);
-            for bind in &process.addresses {
-                let bindport = ip_only(&bind);
-                if bindport.eq("127.0.0.1") || bindport.eq("[::1]") {
-                    // TODO: treat local binds
+        }
+    }
+    ports
+}
+
+/// The network of `network` (an address with prefix length) includes the address `address`.
+fn is_on_subnet(network: &str, address: &str) -> bool {
+    let address = ip_only(&address.replace(['[', ']'], ""));
+    match (IPAddress::parse(network), IPAddress::parse(address)) {
+        (Ok(network), Ok(address)) => {
+            network.is_ipv4() == address.is_ipv4() && network.includes(&address)
+        }
+        _ => false,
+    }
+}
+
+/// The ports if they all belong to one namespace.
+fn unique<'p, 'a>(ports: &[&'p Port<'a>]) -> Option<&'p Port<'a>> {
+    let first = ports.first()?;
+    ports
+        .iter()
+        .all(|port| {
+            port.machine.hostname == first.machine.hostname
+                && port.namespace.name == first.namespace.name
+        })
+        .then_some(*first)
+}
+
+/// Port of `address` as seen from `machine` - an address of the machine itself is not looked
+/// up on other machines.
+///
+/// Addresses found on several machines (docker bridges, overlapping private networks) resolve
+/// to the one on the same subnet as one of `sources`, the addresses of the sending side, and to
+/// none if that is still ambiguous.
+fn find_port<'p, 'a>(
+    ports: &'p [Port<'a>],
+    address: &str,
+    machine: &Machine,
+    sources: &[&String],
+) -> Option<&'p Port<'a>> {
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    let owners: Vec<&Port> = ports
+        .iter()
+        .filter(|port| ip_only(port.address) == address)
+        .collect();
+    let local: Vec<&Port> = owners
+        .iter()
+        .copied()
+        .filter(|port| port.machine.hostname == machine.hostname)
+        .collect();
+    let owners = if local.is_empty() { owners } else { local };
+    unique(&owners).or_else(|| {
+        let nearby: Vec<&Port> = owners
+            .into_iter()
+            .filter(|port| {
+                sources
+                    .iter()
+                    .any(|source| is_on_subnet(port.address, source))
+            })
+            .collect();
+        let found = unique(&nearby);
+        if found.is_none() {
+            log::debug!(
+                "Skipping ambiguous address {} seen on {}",
+                address,
+                machine.hostname
+            );
+        }
+        found
+    })
+}
+
+/// Addresses of the interface `device` of a namespace.
+fn device_addresses<'a>(namespace: &'a Namespace, device: &str) -> Vec<&'a String> {
+    namespace
+        .interfaces
+        .iter()
+        .filter(|interface| interface.name == device)
+        .flat_map(|interface| &interface.addresses)
+        .collect()
+}
+
+/// Edges from the interface of each route to the interface of its gateway - only gateways found
+/// on another machine of the model are drawn, labeled with the routed destinations.
+fn generate_gateways(digraph: &mut Scope, model: &Model, ports: &[Port]) {
+    let mut edges: Vec<(String, String, Vec<&String>)> = vec![];
+    for machine in &model.machines {
+        for namespace in &machine.namespaces {
+            let prefix = namespace_prefix(machine, namespace);
+            for route in &namespace.routes {
+                let (gateway, device) = match (&route.gateway, &route.device) {
+                    (Some(gateway), Some(device)) => (gateway, device),
+                    _ => continue,
+                };
+                let sources = device_addresses(namespace, device);
+                let to = match find_port(ports, gateway, machine, &sources) {
+                    Some(port) if port.machine.hostname != machine.hostname => &port.port,
+                    _ => continue,
+                };
+                let from = format!("{}{}", prefix, node_id(device));
+                match edges
+                    .iter_mut()
+                    .find(|(other_from, other_to, _)| *other_from == from && other_to == to)
+                {
+                    Some((_, _, destinations)) => {
+                        if !destinations.contains(&&route.destination) {
+                            destinations.push(&route.destination)
+                        }
+                    }
+                    None => edges.push((from, to.to_string(), vec![&route.destination])),
+                }
+            }
+        }
+    }
+    for (from, to, destinations) in edges {
+        let label: Vec<&str> = destinations.iter().map(|d| d.as_str()).collect();
+        digraph
+            .edge(from, to)
+            .attributes()
+            .set_label(&label.join("\\n"))
+            .set_style(Style::Dashed)
+            .set_color(Color::Blue);
+    }
+}
+
+/// Undirected edges between the interfaces of machines that see each other in their neighbor
+/// tables - drawn once per pair of interfaces.
+fn generate_neighbors(digraph: &mut Scope, model: &Model, ports: &[Port]) {
+    let mut pairs: Vec<(String, String)> = vec![];
+    for machine in &model.machines {
+        for namespace in &machine.namespaces {
+            let prefix = namespace_prefix(machine, namespace);
+            for neighbor in &namespace.neighbors {
+                let device = match &neighbor.device {
+                    Some(device) if is_reachable(neighbor) => device,
+                    _ => continue,
+                };
+                let sources = device_addresses(namespace, device);
+                let to = match find_port(ports, &neighbor.address, machine, &sources) {
+                    Some(port) if port.machine.hostname != machine.hostname => &port.port,
+                    _ => continue,
+                };
+                let from = format!("{}{}", prefix, node_id(device));
+                let node = to.split(':').next().unwrap_or_default();
+                if pairs.iter().any(|(a, b)| {
+                    (*a == from && b.split(':').next() == Some(node))
+                        || (a == node && b.split(':').next() == Some(&from))
+                }) {
                     continue;
                 }
-                if bindport.eq("*") || bindport.eq("0.0.0.0")  || bindport.eq("[::1]"){
-                    for interface in &machine.interfaces {
-                        // TODO: check IPv4/6 and add clap options
-                        for addr in &interface.addresses {
-                            cluster.edge(
-                                &name,
-                                ip_only(&format!(
+                pairs.push((from, to.to_string()));
+            }
+        }
+    }
+    for (from, to) in pairs {
+        digraph
+            .edge(from, to)
+            .attributes()
+            .set_style(Style::Dotted)
+            .set_color(Color::Grey)
+            .set("dir", "none", false);
+    }
+}
+
+/// Connection states drawn red - the peer does not answer or the process does not close.
+const FAILING_STATES: [&str; 2] = ["SYN-SENT", "CLOSE-WAIT"];
+
+fn is_loopback(addr: &str) -> bool {
+    addr.starts_with("127.") || addr == "[::1]" || addr == "::1"
+}
+
+/// Edges from the processes of connections to the listening processes - to the interface if the
+/// listener is not drawn and to a node of the remote address if it is not in the model or found
+/// on several machines. Edges
+/// of the same processes, port and state are drawn once with their count.
+///
+/// The accepting side of a connection is only drawn for clients outside of the model.
+fn generate_connections(
+    digraph: &mut Scope,
+    model: &Model,
+    drawn: &[String],
+    ports: &[Port],
+    options: &GraphOptions,
+) {
+    let mut remotes: Vec<String> = vec![];
+    let mut edges: Vec<(String, String, &str, &str, usize)> = vec![];
+    for connection in &model.connections {
+        let (machine, namespace) = match model.machines.iter().find_map(|machine| {
+            machine
+                .namespaces
+                .iter()
+                .find(|ns| is_connection_of(machine, connection) && ns.name == connection.namespace)
+                .map(|namespace| (machine, namespace))
+        }) {
+            Some(found) => found,
+            None => continue,
+        };
+        let prefix = namespace_prefix(machine, namespace);
+        let (node, _) = user_node(&prefix, &connection_user(connection), namespace, options);
+        let remote = match is_loopback(&connection.remote_addr) {
+            true => Some((machine, namespace)),
+            false => find_port(
+                ports,
+                &connection.remote_addr,
+                machine,
+                &[&connection.local_addr],
+            )
+            .map(|port| (port.machine, port.namespace)),
+        };
+        let remote_node = format!("\"{}\"", escape_quoted(&connection.remote_addr));
+        let (from, to, port) = if is_server_side(namespace, connection) {
+            if remote.is_some() {
+                continue;
+            }
+            (remote_node, node, connection.local_port.as_str())
+        } else {
+            let to = match remote {
+                Some((machine, namespace)) => {
+                    let prefix = namespace_prefix(machine, namespace);
+                    let listener =
+                        find_listener(namespace, &connection.remote_addr, &connection.remote_port)
+                            .map(|process| {
+                                let user = SocketUser {
+                                    name: process.name.to_string(),
+                                    pid: process.pid,
+                                    fd: None,
+                                    unit: process.unit.clone(),
+                                };
+                                user_node(&prefix, &user, namespace, options).0
+                            })
+                            .filter(|name| drawn.contains(name));
+                    match listener {
+                        Some(listener) => listener,
+                        None => {
+                            let addr = connection.remote_addr.trim_start_matches('[');
+                            let addr = addr.trim_end_matches(']');
+                            let interface = namespace.interfaces.iter().find(|interface| {
+                                interface.addresses.iter().any(|a| ip_only(a) == addr)
+                            });
+                            match interface {
+                                Some(interface) => format!(
                                     "{}{}:\"{}\"",
-                                    machine.hostname,
-                                    sanitiza_label(&ip_only(&interface.name)),
-                                    sanitiza_label(&ip_only(&addr))
-                                )),
-                            );
+                                    prefix,
+                                    node_id(&interface.name),
+                                    sanitiza_label(addr)
+                                ),
+                                None => continue,
+                            }
                         }
                     }
-                } else {
-                    cluster.edge(
-                        &name,
-                        ip_only(&format!(
-                            "{}{}:\"{}\"",
-                            machine.hostname,
-                            "TODO",
-                            sanitiza_label(&ip_only(&bindport))
-                        )),
-                    );
                 }
+                None => remote_node,
+            };
+            (node, to, connection.remote_port.as_str())
+        };
+        if !drawn.contains(&from) && !from.starts_with('"') {
+            continue;
+        }
+        for remote in [&from, &to] {
+            if remote.starts_with('"') && !remotes.contains(remote) {
+                remotes.push(remote.to_string());
             }
         }
+        let state = connection.state.as_str();
+        match edges
+            .iter_mut()
+            .find(|edge| edge.0 == from && edge.1 == to && edge.2 == port && edge.3 == state)
+        {
+            Some(edge) => edge.4 += 1,
+            None => edges.push((from, to, port, state, 1)),
+        }
+    }
+    for remote in remotes {
+        let label = remote.trim_matches('"').to_string();
+        digraph
+            .node_named(remote)
+            .set_label(&label)
+            .set_shape(Shape::Rectangle);
+    }
+    for (from, to, port, state, count) in edges {
+        let mut label = format!(":{}", port);
+        if state != "ESTAB" {
+            label.push_str(&format!(" {}", state));
+        }
+        if count > 1 {
+            label.push_str(&format!(" ({})", count));
+        }
+        let edge = digraph.edge(from, to);
+        let mut attributes = edge.attributes();
+        attributes.set_label(&label);
+        if FAILING_STATES.contains(&state) {
+            attributes.set_style(Style::Dashed).set_color(Color::Red);
+        } else if state != "ESTAB" {
+            attributes.set_style(Style::Dashed);
+        }
     }
 }
 
-pub fn generate_graph(model: &Model) -> String {
+pub fn generate_graph(model: &Model, options: &GraphOptions) -> String {
     let mut output_bytes = Vec::new();
     {
         let mut writer = DotWriter::from(&mut output_bytes);
         writer.set_pretty_print(true);
         let mut digraph = writer.digraph();
         digraph.set_rank_direction(dot_writer::RankDirection::LeftRight);
+        let mut drawn = vec![];
         for machine in &model.machines {
-            generate_machine_node(&mut digraph, &machine)
+            drawn.extend(generate_machine_node(
+                &mut digraph,
+                machine,
+                &model.connections,
+                options,
+            ));
         }
+        let ports = interface_ports(model);
+        generate_connections(&mut digraph, model, &drawn, &ports, options);
+        generate_gateways(&mut digraph, model, &ports);
+        generate_neighbors(&mut digraph, model, &ports);
     }
     String::from_utf8(output_bytes).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn machine(
+        hostname: &str,
+        interfaces: serde_json::Value,
+        routes: serde_json::Value,
+    ) -> serde_json::Value {
+        json!({
+            "hostname": hostname,
+            "namespaces": [{
+                "name": ROOT,
+                "interfaces": interfaces,
+                "routes": routes,
+                "processes": [],
+            }],
+        })
+    }
+
+    fn graph(machines: Vec<serde_json::Value>) -> String {
+        let model: Model =
+            serde_json::from_value(json!({ "machines": machines, "connections": [] })).unwrap();
+        let options = GraphOptions {
+            group_by: GroupBy::Process,
+            listeners: Listeners::All,
+            ipc: true,
+        };
+        generate_graph(&model, &options)
+    }
+
+    #[test]
+    fn prefers_gateways_on_the_same_subnet() {
+        let output = graph(vec![
+            machine(
+                "client",
+                json!([{ "name": "eth0", "addresses": ["10.0.0.5/24"] }]),
+                json!([{ "destination": "default", "gateway": "10.0.0.1", "device": "eth0", "table": "main" }]),
+            ),
+            machine(
+                "gw1",
+                json!([{ "name": "eth0", "addresses": ["10.0.0.1/24"] }]),
+                json!([]),
+            ),
+            machine(
+                "gw2",
+                json!([{ "name": "eth0", "addresses": ["10.0.0.1/30"] }]),
+                json!([]),
+            ),
+        ]);
+        assert!(output.contains("clienteth0 -> gw1eth0:\"10.0.0.1\""));
+        assert!(!output.contains("-> gw2eth0"));
+    }
+
+    #[test]
+    fn skips_ambiguous_gateways() {
+        let docker = json!([{ "name": "docker0", "addresses": ["172.17.0.1/16"] }]);
+        let output = graph(vec![
+            machine(
+                "client",
+                json!([{ "name": "eth0", "addresses": ["172.17.0.5/16"] }]),
+                json!([{ "destination": "default", "gateway": "172.17.0.1", "device": "eth0", "table": "main" }]),
+            ),
+            machine("host1", docker.clone(), json!([])),
+            machine("host2", docker, json!([])),
+        ]);
+        assert!(!output.contains("clienteth0 ->"));
+    }
+
+    #[test]
+    fn resolves_remote_links_by_netnsid() {
+        let link = |name: &str, index: u32, link_index: Option<u32>, netnsid: Option<i32>| {
+            json!({ "name": name, "addresses": [], "index": index,
+                    "link_index": link_index, "link_netnsid": netnsid })
+        };
+        let namespace = |name: &str, interfaces: serde_json::Value| json!({ "name": name, "interfaces": interfaces, "processes": [] });
+        let model: Model = serde_json::from_value(json!({
+            "machines": [{
+                "hostname": "host",
+                "namespaces": [
+                    namespace(ROOT, json!([link("eth0", 5, None, None), link("veth7", 7, Some(3), Some(0))])),
+                    namespace("a", json!([link("eth1", 3, Some(7), Some(0)), link("mv0", 4, Some(5), Some(0))])),
+                    namespace("b", json!([link("eth5", 5, None, None), link("eth6", 6, Some(5), None)])),
+                ],
+            }],
+            "connections": [],
+        }))
+        .unwrap();
+        let options = GraphOptions {
+            group_by: GroupBy::Process,
+            listeners: Listeners::All,
+            ipc: true,
+        };
+        let output = generate_graph(&model, &options);
+        // the veth peer points back
+        assert!(output.contains("hostveth7 -> host_a_eth1 [label=\"veth\""));
+        // netnsid 0 of namespace a is the root namespace, not b with the same ifindex
+        assert!(output.contains("host_a_mv0 -> hosteth0"));
+        assert!(!output.contains("host_a_mv0 -> host_b_eth5"));
+        // a single candidate that does not point back is not guessed
+        assert!(!output.contains("host_b_eth6 ->"));
+    }
+
+    #[test]
+    fn draws_ipc_edges_unless_disabled() {
+        let user = |name: &str, pid: u32| json!([{ "name": name, "pid": pid, "fd": 3 }]);
+        let socket = |state: &str, path: Option<&str>, inode: u64, peer: Option<u64>, users| {
+            json!({ "kind": "u_str", "state": state, "path": path, "inode": inode,
+                    "peer": peer, "users": users })
+        };
+        let model: Model = serde_json::from_value(json!({
+            "machines": [{
+                "hostname": "host",
+                "namespaces": [{
+                    "name": ROOT,
+                    "interfaces": [],
+                    "processes": [],
+                    "unix_sockets": [
+                        socket("LISTEN", Some("/run/docker.sock"), 21050, None, user("dockerd", 912)),
+                        socket("ESTAB", Some("/run/docker.sock"), 38211, Some(38210), user("dockerd", 912)),
+                        socket("ESTAB", None, 38210, Some(38211), user("docker", 4200)),
+                    ],
+                }],
+            }],
+            "connections": [],
+        }))
+        .unwrap();
+        let mut options = GraphOptions {
+            group_by: GroupBy::Process,
+            listeners: Listeners::All,
+            ipc: true,
+        };
+        let output = generate_graph(&model, &options);
+        assert!(output.contains("label=\"/run/docker.sock\""));
+        options.ipc = false;
+        let output = generate_graph(&model, &options);
+        assert!(!output.contains("/run/docker.sock"));
+    }
+
+    #[test]
+    fn binds_ipv6_wildcards_to_all_interfaces() {
+        let mut host = machine(
+            "host",
+            json!([{ "name": "eth0", "addresses": ["10.0.0.5/24"] }]),
+            json!([]),
+        );
+        host["namespaces"][0]["processes"] =
+            json!([{ "name": "sshd", "pid": 812, "addresses": ["[::]:22/tcp"] }]);
+        let output = graph(vec![host]);
+        assert!(output.contains("hostsshd -> hosteth0:\"10.0.0.5\""));
+    }
+
+    /// Unquoted node IDs in node statements and edges are plain DOT IDs.
+    fn assert_valid_ids(output: &str) {
+        let re = Regex::new(r#"^\s*(?P<from>[^\s\[]+)( -> (?P<to>[^\s\[:]+))?"#).unwrap();
+        let id = Regex::new(r#"^("[^"]*"|[A-Za-z_][A-Za-z0-9_]*)$"#).unwrap();
+        for line in output
+            .lines()
+            .filter(|line| line.trim_end().ends_with("];"))
+        {
+            let cap = re.captures(line).unwrap();
+            let from = cap["from"].split(':').next().unwrap();
+            assert!(id.is_match(from), "invalid node ID in: {}", line);
+            if let Some(to) = cap.name("to") {
+                assert!(id.is_match(to.as_str()), "invalid node ID in: {}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn builds_valid_node_ids() {
+        let mut host = machine(
+            "db1.example.com",
+            json!([{ "name": "eth0.100", "addresses": ["10.0.0.5/24"] }]),
+            json!([]),
+        );
+        host["namespaces"][0]["processes"] = json!([
+            { "name": ".anthropic_stdi", "pid": 1, "addresses": ["0.0.0.0:8080/tcp"] },
+            { "name": "php-fpm", "pid": 2, "unit": "php-fpm@blue.service",
+              "addresses": ["10.0.0.5:9000/tcp"] },
+        ]);
+        let model: Model =
+            serde_json::from_value(json!({ "machines": [host], "connections": [] })).unwrap();
+        for group_by in [GroupBy::Process, GroupBy::Unit] {
+            let options = GraphOptions {
+                group_by,
+                listeners: Listeners::All,
+                ipc: true,
+            };
+            let output = generate_graph(&model, &options);
+            assert_valid_ids(&output);
+            assert!(output.contains("label=\".anthropic_stdi\""));
+        }
+    }
+
+    #[test]
+    fn connects_unit_nodes_with_valid_ids() {
+        let mut client = machine(
+            "client",
+            json!([{ "name": "eth0", "addresses": ["10.0.0.9/24"] }]),
+            json!([]),
+        );
+        client["namespaces"][0]["processes"] = json!([]);
+        let mut server = machine(
+            "server",
+            json!([{ "name": "eth0", "addresses": ["10.0.0.5/24"] }]),
+            json!([]),
+        );
+        server["namespaces"][0]["processes"] = json!([{ "name": "myapp", "pid": 7,
+            "unit": "myapp@blue.service", "addresses": ["0.0.0.0:8080/tcp"] }]);
+        let model: Model = serde_json::from_value(json!({
+            "machines": [client, server],
+            "connections": [{ "host": "client", "process": "curl",
+                "users": [{ "name": "curl", "pid": 42, "fd": 3, "unit": "session-3.scope" }],
+                "local_addr": "10.0.0.9", "local_port": "40000",
+                "remote_addr": "10.0.0.5", "remote_port": "8080", "state": "ESTAB" }],
+        }))
+        .unwrap();
+        let options = GraphOptions {
+            group_by: GroupBy::Unit,
+            listeners: Listeners::All,
+            ipc: true,
+        };
+        let output = generate_graph(&model, &options);
+        assert_valid_ids(&output);
+        assert!(output.contains("clientsession3_scope -> servermyappblue_service"));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::exclude::is_device_excluded;
+use crate::model::Interface;
+use serde::Deserialize;
+
+/// Links with details and addresses - the brief format for `ip` without JSON support.
+pub const SECTIONS: [(&str, &str); 1] = [(
+    "interfaces",
+    "ip -j -d address show 2>/dev/null || ip --brief address show",
+)];
+
+/// A link of `ip -j -d address show` - only the used fields.
+#[derive(Debug, Deserialize)]
+struct IpLink {
+    ifname: String,
+    #[serde(default)]
+    operstate: Option<String>,
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default)]
+    mtu: Option<u32>,
+    #[serde(default)]
+    link_type: Option<String>,
+    #[serde(default)]
+    ifindex: Option<u32>,
+    #[serde(default)]
+    master: Option<String>,
+    #[serde(default)]
+    link: Option<String>,
+    #[serde(default)]
+    link_index: Option<u32>,
+    #[serde(default)]
+    link_netnsid: Option<i32>,
+    #[serde(default)]
+    linkinfo: Option<IpLinkInfo>,
+    #[serde(default)]
+    addr_info: Vec<IpAddrInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IpLinkInfo {
+    #[serde(default)]
+    info_kind: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IpAddrInfo {
+    #[serde(default)]
+    local: Option<String>,
+    #[serde(default)]
+    prefixlen: Option<u8>,
+}
+
+/// Parse `ip -j -d address show` - addresses use the brief notation, e.g. `10.0.0.1/24`.
+fn parse_json(content: &str) -> Result<Vec<Interface>, serde_json::Error> {
+    let links: Vec<IpLink> = serde_json::from_str(content)?;
+    Ok(links
+        .into_iter()
+        .map(|link| Interface {
+            name: link.ifname,
+            addresses: link
+                .addr_info
+                .into_iter()
+                .filter_map(|info| match (info.local, info.prefixlen) {
+                    (Some(local), Some(prefixlen)) => Some(format!("{}/{}", local, prefixlen)),
+                    (local, _) => local,
+                })
+                .collect(),
+            state: link.operstate,
+            // links without hardware address report zeros or nothing
+            mac: link
+                .address
+                .filter(|mac| mac.chars().any(|c| c != '0' && c != ':')),
+            mtu: link.mtu,
+            link_type: link.link_type,
+            kind: link.linkinfo.and_then(|info| info.info_kind),
+            index: link.ifindex,
+            master: link.master,
+            link: link.link,
+            link_index: link.link_index,
+            link_netnsid: link.link_netnsid,
+        })
+        .collect())
+}
+
+/// Split `NAME@PARENT` - a parent `ifN` is the ifindex of a link in another namespace.
+fn parse_brief_name(interface: &mut Interface, field: &str) {
+    let (name, parent) = match field.split_once('@') {
+        Some((name, parent)) => (name, Some(parent)),
+        None => (field, None),
+    };
+    interface.name = name.to_string();
+    match parent {
+        None | Some("NONE") => {}
+        Some(parent) => match parent.strip_prefix("if").map(|index| index.parse()) {
+            Some(Ok(index)) => interface.link_index = Some(index),
+            _ => interface.link = Some(parent.to_string()),
+        },
+    }
+}
+
+/// Parse `ip --brief address show`, e.g. `eth0@if5  UP  10.0.0.1/24 fe80::1/64` - the brief
+/// format has no ifindex, masters and kinds.
+fn parse_brief(content: &str) -> Vec<Interface> {
+    let mut interfaces = vec![];
+    for line in content.lines() {
+        let mut interface = Interface::default();
+        for (index, field) in line.split_whitespace().enumerate() {
+            match index {
+                0 => parse_brief_name(&mut interface, field),
+                1 => interface.state = Some(field.to_string()),
+                _ => interface.addresses.push(field.to_string()),
+            }
+        }
+        if !interface.name.is_empty() {
+            interfaces.push(interface);
+        }
+    }
+    interfaces
+}
+
+/// Parse the `SECTIONS` output in either format - fails if the JSON format cannot be parsed.
+pub fn parse_interfaces(
+    content: &str,
+    host: &str,
+    excludes: &Option<String>,
+) -> Result<Vec<Interface>, serde_json::Error> {
+    let interfaces = if content.trim_start().starts_with('[') {
+        parse_json(content)?
+    } else {
+        parse_brief(content)
+    };
+    Ok(interfaces
+        .into_iter()
+        .filter(|interface| !is_device_excluded(host, &interface.name, excludes))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ip -j -d address show` of a host with a bridge and the veth of a container - shortened.
+    const JSON: &str = r#"[
+{"ifindex":1,"ifname":"lo","flags":["LOOPBACK","UP"],"mtu":65536,"operstate":"UNKNOWN","link_type":"loopback","address":"00:00:00:00:00:00","addr_info":[{"family":"inet","local":"127.0.0.1","prefixlen":8},{"family":"inet6","local":"::1","prefixlen":128}]},
+{"ifindex":2,"ifname":"eth0","mtu":1500,"operstate":"UP","link_type":"ether","address":"52:54:00:12:34:56","addr_info":[{"family":"inet","local":"10.0.0.5","prefixlen":24}]},
+{"ifindex":3,"ifname":"docker0","mtu":1500,"operstate":"UP","link_type":"ether","address":"02:42:ac:11:00:01","linkinfo":{"info_kind":"bridge"},"addr_info":[{"family":"inet","local":"172.17.0.1","prefixlen":16}]},
+{"ifindex":5,"link_index":4,"ifname":"veth1a2b3c","mtu":1500,"master":"docker0","operstate":"UP","link_type":"ether","address":"6a:1f:22:33:44:55","link_netnsid":0,"linkinfo":{"info_kind":"veth"},"addr_info":[]}
+]"#;
+
+    /// `ip --brief address show` of `ip` without JSON support.
+    const BRIEF: &str = "\
+lo               UNKNOWN        127.0.0.1/8 ::1/128
+eth0             UP             10.0.0.5/24 fe80::5054:ff:fe12:3456/64
+eth0.100@eth0    UP             10.100.0.5/24
+veth1a2b3c@if4   UP
+";
+
+    #[test]
+    fn parses_json() {
+        let interfaces = parse_interfaces(JSON, "vm", &None).unwrap();
+        assert_eq!(interfaces.len(), 4);
+        assert_eq!(interfaces[0].addresses, vec!["127.0.0.1/8", "::1/128"]);
+        assert_eq!(interfaces[0].mac, None);
+        assert_eq!(interfaces[1].mac.as_deref(), Some("52:54:00:12:34:56"));
+        assert_eq!(interfaces[2].kind.as_deref(), Some("bridge"));
+        let veth = &interfaces[3];
+        assert_eq!(veth.kind.as_deref(), Some("veth"));
+        assert_eq!(veth.master.as_deref(), Some("docker0"));
+        assert_eq!((veth.index, veth.link_index), (Some(5), Some(4)));
+        assert_eq!(veth.link_netnsid, Some(0));
+    }
+
+    #[test]
+    fn parses_brief() {
+        let interfaces = parse_interfaces(BRIEF, "vm", &None).unwrap();
+        assert_eq!(interfaces.len(), 4);
+        assert_eq!(interfaces[1].state.as_deref(), Some("UP"));
+        assert_eq!(interfaces[2].name, "eth0.100");
+        assert_eq!(interfaces[2].link.as_deref(), Some("eth0"));
+        assert_eq!(interfaces[3].link_index, Some(4));
+        assert!(interfaces[3].addresses.is_empty());
+    }
+
+    #[test]
+    fn excludes_devices() {
+        let excludes = Some(String::from("vm%lo,other%eth0"));
+        let interfaces = parse_interfaces(BRIEF, "vm", &excludes).unwrap();
+        assert_eq!(interfaces[0].name, "eth0");
+        assert_eq!(interfaces.len(), 3);
+    }
+
+    #[test]
+    fn fails_on_broken_json() {
+        assert!(parse_interfaces(r#"[{"ifname": "eth0", "mtu": "#, "vm", &None).is_err());
+    }
+}
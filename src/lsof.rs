@@ -0,0 +1,125 @@
+use crate::cli::Socket;
+use crate::model::SocketUser;
+use crate::netstat::{split_endpoint, state_name};
+
+/// `*:22` of an IPv4 socket as `0.0.0.0:22` - the wildcard alone is taken as `[::]`.
+fn wildcard(endpoint: &str, family: &str) -> String {
+    match endpoint.strip_prefix("*:") {
+        Some(port) if family == "IPv4" => format!("0.0.0.0:{}", port),
+        _ => endpoint.to_string(),
+    }
+}
+
+/// Parse the output of `lsof -i -n -P`.
+///
+/// The NAME column is `local[->remote] [(STATE)]`, the columns before it are located via the
+/// `IPv4`/`IPv6` TYPE and the `TCP`/`UDP` NODE fields as SIZE/OFF is not always present.
+pub fn parse_sockets(content: &str) -> Vec<Socket> {
+    let mut sockets = vec![];
+    for line in content.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let family = fields.iter().position(|f| *f == "IPv4" || *f == "IPv6");
+        let node = fields.iter().position(|f| *f == "TCP" || *f == "UDP");
+        let (family, node) = match (family, node) {
+            (Some(family), Some(node)) if node > family && node + 1 < fields.len() => {
+                (fields[family], node)
+            }
+            _ => {
+                log::debug!("Skipping lsof line: {}", line);
+                continue;
+            }
+        };
+        let protocol = fields[node].to_lowercase();
+        let (local, remote) = match fields[node + 1].split_once("->") {
+            Some((local, remote)) => (local, Some(remote)),
+            None => (fields[node + 1], None),
+        };
+        let local = match split_endpoint(&wildcard(local, family)) {
+            Some(local) => local,
+            None => {
+                log::warn!("Cannot parse lsof line: {}", line);
+                continue;
+            }
+        };
+        let remote = remote
+            .and_then(|remote| split_endpoint(&wildcard(remote, family)))
+            .unwrap_or_else(|| split_endpoint(&wildcard("*:*", family)).unwrap_or_default());
+        let state = match fields.get(node + 2) {
+            Some(state) => state_name(state.trim_start_matches('(').trim_end_matches(')')),
+            None if remote.1 == "*" => String::from("UNCONN"),
+            None => String::from("ESTAB"),
+        };
+        sockets.push(Socket {
+            protocol,
+            state,
+            local_addr: local.0,
+            local_port: local.1,
+            remote_addr: remote.0,
+            remote_port: remote.1,
+            users: vec![SocketUser {
+                name: fields[0].replace("\\x20", " "),
+                pid: fields[1].parse().ok(),
+                fd: fields[3]
+                    .trim_end_matches(|c: char| !c.is_ascii_digit())
+                    .parse()
+                    .ok(),
+                unit: None,
+            }],
+        });
+    }
+    sockets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `lsof -i -n -P` of lsof 4.95 - macOS and older versions lack the SIZE/OFF column.
+    const LSOF: &str = "\
+COMMAND    PID     USER   FD   TYPE DEVICE SIZE/OFF NODE NAME
+sshd       812     root    3u  IPv4  21345      0t0  TCP *:22 (LISTEN)
+sshd       812     root    4u  IPv6  21347      0t0  TCP *:22 (LISTEN)
+sshd      1404     root    4u  IPv4  31337      0t0  TCP 10.0.0.5:22->10.0.0.9:51234 (ESTABLISHED)
+nginx     2100 www-data    6u  IPv6  41000      0t0  TCP [2001:db8::5]:443->[2001:db8::9]:40112 (ESTABLISHED)
+psql      2001    alice    3u  IPv4  41234      0t0  TCP 10.0.0.5:40022->10.0.0.7:5432 (SYN_SENT)
+chronyd    700   chrony    5u  IPv4  18000      0t0  UDP 127.0.0.1:323
+Web\\x20Con 3000   alice   90u  IPv6 0x1234abcd      TCP [fe80::1%en0]:50000->[fe80::2%en0]:443 (CLOSE_WAIT)
+";
+
+    #[test]
+    fn parses_listeners() {
+        let sockets = parse_sockets(LSOF);
+        assert_eq!(sockets.len(), 7);
+        assert_eq!(sockets[0].to_string(), "tcp LISTEN 0.0.0.0:22 0.0.0.0:*");
+        assert_eq!(sockets[1].to_string(), "tcp LISTEN [::]:22 [::]:*");
+        assert_eq!(sockets[1].users[0].name, "sshd");
+        assert_eq!(sockets[1].users[0].pid, Some(812));
+        assert_eq!(sockets[1].users[0].fd, Some(4));
+        assert_eq!(sockets[5].to_string(), "udp UNCONN 127.0.0.1:323 0.0.0.0:*");
+    }
+
+    #[test]
+    fn strips_states() {
+        let sockets = parse_sockets(LSOF);
+        assert_eq!(
+            sockets[2].to_string(),
+            "tcp ESTAB 10.0.0.5:22 10.0.0.9:51234"
+        );
+        assert_eq!(sockets[4].state, "SYN-SENT");
+        assert_eq!(sockets[6].state, "CLOSE-WAIT");
+    }
+
+    #[test]
+    fn parses_ipv6_endpoints() {
+        let sockets = parse_sockets(LSOF);
+        assert_eq!(
+            sockets[3].to_string(),
+            "tcp ESTAB [2001:db8::5]:443 [2001:db8::9]:40112"
+        );
+        assert_eq!(
+            sockets[6].to_string(),
+            "tcp CLOSE-WAIT [fe80::1%en0]:50000 [fe80::2%en0]:443"
+        );
+        assert_eq!(sockets[6].users[0].name, "Web Con");
+    }
+}
@@ -1,14 +1,35 @@
 use clap::Parser;
 use log::LevelFilter;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::prelude::*;
 use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
 
 mod cli;
+mod container;
 mod exclude;
+mod firewall;
 mod graph;
+mod link;
+mod lsof;
 mod model;
+#[cfg(feature = "native-ssh")]
+mod native;
+mod neighbor;
+mod netns;
+mod netstat;
 mod network;
+mod pool;
+mod procinfo;
+mod procnet;
+mod route;
+mod settings;
+mod ss;
+mod ssh;
+mod systemd;
+mod transport;
+mod unix;
 
 #[derive(Parser, Debug)]
 #[clap(author, about, version)]
@@ -90,6 +111,128 @@ struct Opts {
     /// only load from file
     #[clap(long)]
     offline: bool,
+    /// Transport used to reach hosts - repeat for per host overrides
+    ///
+    /// Syntax:
+    ///
+    ///     --transport [HOST=]ssh|native|local|cmd:<template>
+    ///
+    /// Examples:
+    ///
+    ///     --transport local                              - run all commands locally
+    ///
+    ///     --transport host123=local                      - run commands for <host123> locally
+    ///
+    ///     --transport 'db1=cmd:docker exec {host} sh -c' - reach <db1> via a command template
+    ///
+    /// `{host}` is replaced by the host and `{cmd}` by the command, which is appended otherwise.
+    /// native is the built-in ssh client, available with the native-ssh cargo feature.
+    #[clap(short, long, multiple_occurrences(true))]
+    transport: Vec<transport::TransportRule>,
+    /// Collect the local machine directly (no ssh) and crawl on from there
+    #[clap(short, long)]
+    local: bool,
+    /// Source of the socket tables: auto, ss (iproute2), proc (/proc/net), netstat or lsof
+    ///
+    /// auto picks the first of ss, netstat and lsof found on the host and falls back to proc.
+    #[clap(long, default_value = "auto")]
+    collector: cli::Collector,
+    /// Connection states to collect, e.g. ESTAB,SYN-SENT,CLOSE-WAIT,TIME-WAIT
+    ///
+    /// Connections stuck in SYN-SENT or CLOSE-WAIT are drawn red and dashed, other states than
+    /// ESTAB dashed. Only the peers of ESTAB connections are crawled.
+    #[clap(long, default_value = "ESTAB")]
+    states: cli::States,
+    /// Collect all network namespaces (ip netns, containers) - requires root on the hosts
+    #[clap(long)]
+    namespaces: bool,
+    /// Crawl the reachable hosts of the neighbor tables too - not only the peers of connections
+    ///
+    /// Link-local addresses are skipped, they are not usable without an interface.
+    #[clap(long)]
+    crawl_neighbors: bool,
+    /// Aggregate process nodes by process name or by systemd unit
+    #[clap(long, default_value = "process")]
+    group_by: graph::GroupBy,
+    /// Listeners drawn: all, reachable (not firewalled) or firewalled
+    ///
+    /// Listeners are evaluated against the nftables or iptables rules of their host - reading the
+    /// rules requires root, see --sudo. Restricted listeners are drawn dashed orange, firewalled
+    /// ones dashed red.
+    #[clap(long, default_value = "all")]
+    listeners: graph::Listeners,
+    /// Do not draw the IPC edges between the processes connected through unix sockets
+    #[clap(long)]
+    no_ipc: bool,
+    /// Number of hosts collected in parallel
+    #[clap(short, long, default_value = "8")]
+    jobs: usize,
+    /// Login name for ssh
+    #[clap(long)]
+    ssh_user: Option<String>,
+    /// Port for ssh
+    #[clap(long)]
+    ssh_port: Option<u16>,
+    /// Identity file for ssh
+    #[clap(long)]
+    identity: Option<String>,
+    /// Jump host(s) for ssh, e.g. bastion or admin@bastion:2222
+    #[clap(long)]
+    jump: Option<String>,
+    /// Extra ssh option, e.g. --ssh-option Ciphers=aes256-ctr - repeat for several options
+    #[clap(long, multiple_occurrences(true))]
+    ssh_option: Vec<String>,
+    /// SSH options for a host or network - repeat for several rules
+    ///
+    /// Syntax:
+    ///
+    ///     --ssh-rule HOST|NETWORK,KEY=VALUE[,KEY=VALUE...]
+    ///
+    /// Keys are user, port, identity, jump and option. Values containing commas are quoted. All
+    /// matching rules apply in their order, after the rules of the config file.
+    ///
+    /// Examples:
+    ///
+    ///     --ssh-rule 10.20.0.0/16,jump=bastion       - reach 10.20.0.0/16 via <bastion>
+    ///
+    ///     --ssh-rule db1,user=postgres,port=2222     - login to <db1> as postgres on port 2222
+    ///
+    ///     --ssh-rule 'db1,option="Ciphers=aes128-ctr,aes256-ctr"' - restrict the ciphers for <db1>
+    #[clap(long, multiple_occurrences(true))]
+    ssh_rule: Vec<ssh::SshRule>,
+    /// Config file (JSON) with ssh rules
+    ///
+    /// Example:
+    ///
+    ///     {"ssh": [{"network": "10.20.0.0/16", "jump": "bastion"}]}
+    #[clap(long)]
+    config: Option<String>,
+    /// Reach discovered hosts through the host that saw the connection (ssh ProxyJump chain)
+    ///
+    /// The jump hosts are recorded in model.json and reused for the initial hosts on a rescan.
+    #[clap(long)]
+    chain: bool,
+    /// Seconds to wait for an ssh connection
+    #[clap(long, default_value = "10")]
+    connect_timeout: u64,
+    /// Seconds a single command may take - no limit otherwise
+    #[clap(long)]
+    command_timeout: Option<u64>,
+    /// Seconds all commands of a host may take - no limit otherwise
+    ///
+    /// Hosts exceeding a timeout are recorded as unreachable.
+    #[clap(long)]
+    host_timeout: Option<u64>,
+    /// Host key check: strict (known hosts only), accept-new (add unknown keys) or ignore (lab use)
+    ///
+    /// ssh runs in batch mode - it never asks for passwords or to confirm host keys.
+    #[clap(long, default_value = "strict")]
+    host_key_policy: ssh::HostKeyPolicy,
+    /// Run the commands with sudo - without root the processes of other users' sockets are unknown
+    ///
+    /// sudo must not ask for a password, hosts where it does are collected as the login user.
+    #[clap(long)]
+    sudo: bool,
 }
 
 fn init_logging(verbosity: i32) {
@@ -113,6 +256,20 @@ fn init_logging(verbosity: i32) {
     log::debug!("Initialized logger!");
 }
 
+/// Jump hosts recorded by a previous run - none if there is no readable model.
+fn read_jumps(filename: &str) -> HashMap<String, Vec<String>> {
+    if !Path::new(filename).exists() {
+        return HashMap::new();
+    }
+    match model::Model::load(filename) {
+        Ok(model) => model.jumps(),
+        Err(err) => {
+            log::warn!("Cannot read jump hosts: {}", err);
+            HashMap::new()
+        }
+    }
+}
+
 fn main() {
     let opts: Opts = Opts::parse();
     init_logging(opts.verbose);
@@ -120,18 +277,65 @@ fn main() {
 
     let mut model = model::Model::new();
     if opts.offline {
-        let mut file = File::open("model.json").unwrap();
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer).unwrap();
-        model = serde_json::from_str(&buffer).unwrap();
+        model = model::Model::load("model.json").unwrap_or_else(|err| {
+            log::error!("{}", err);
+            std::process::exit(1);
+        });
     } else {
-        for host in opts.hosts {
-            model.add_machine(&host, &opts.excludes, &opts.networks);
-        }
+        let config = match &opts.config {
+            Some(filename) => settings::Config::load(filename).unwrap_or_else(|err| {
+                log::error!("{}", err);
+                std::process::exit(1);
+            }),
+            None => settings::Config::default(),
+        };
+        let mut ssh = ssh::SshConfig {
+            defaults: ssh::SshOptions {
+                user: opts.ssh_user,
+                port: opts.ssh_port,
+                identity: opts.identity,
+                jump: opts.jump,
+                options: opts.ssh_option,
+            },
+            rules: config.ssh,
+            policy: ssh::SshPolicy {
+                connect_timeout: Duration::from_secs(opts.connect_timeout),
+                host_key: opts.host_key_policy,
+            },
+        };
+        let timeouts = transport::Timeouts {
+            command: opts.command_timeout.map(Duration::from_secs),
+            host: opts.host_timeout.map(Duration::from_secs),
+        };
+        ssh.rules.extend(opts.ssh_rule);
+        let jumps = if opts.chain {
+            read_jumps("model.json")
+        } else {
+            HashMap::new()
+        };
+        let settings = settings::Settings {
+            excludes: opts.excludes,
+            networks: opts.networks,
+            transports: transport::Transports::new(opts.transport, ssh, timeouts),
+            collector: opts.collector,
+            states: opts.states,
+            namespaces: opts.namespaces,
+            neighbors: opts.crawl_neighbors,
+            jobs: opts.jobs,
+            chain: opts.chain,
+            sudo: opts.sudo,
+            jumps,
+        };
+        model.crawl(&opts.hosts, opts.local, &settings);
         let serialized = serde_json::to_string(&model).unwrap();
         let mut file = File::create("model.json").unwrap();
         file.write_all(serialized.as_bytes()).unwrap();
     }
     log::debug!("Model: {:?}", model);
-    model.generate(&opts.output);
+    let options = graph::GraphOptions {
+        group_by: opts.group_by,
+        listeners: opts.listeners,
+        ipc: !opts.no_ipc,
+    };
+    model.generate(&opts.output, &options);
 }
@@ -1,8 +1,18 @@
-use crate::cli::{get_connections, get_hostname, get_interfaces, get_processes};
+use crate::cli::{
+    add_process_info, host_script, namespaces_script, parse_namespace, run_script, section,
+    subsections, sudo_script, CollectError,
+};
 use crate::exclude::is_host_excluded;
-use crate::graph::generate_graph;
-use crate::network::is_host_in_network;
+use crate::graph::{generate_graph, GraphOptions};
+use crate::neighbor::{is_link_local, is_reachable};
+use crate::netns::{parse_namespaces, NetNs, ROOT};
+use crate::network::{is_host_in_network, unbracket};
+use crate::pool;
+use crate::settings::Settings;
+use crate::transport::Transport;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
 
@@ -15,30 +25,501 @@ pub struct Model {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Machine {
     pub hostname: String,
+    /// host as it was reached - none for the local machine
+    #[serde(default)]
+    pub address: Option<String>,
+    /// jump hosts the machine was reached through (ssh ProxyJump)
+    #[serde(default)]
+    pub jump: Vec<String>,
+    #[serde(default)]
+    pub status: Status,
+    /// what could not be collected
+    #[serde(default)]
+    pub errors: Vec<String>,
+    /// sockets without process - the owners of sockets of other users are only visible to root
+    #[serde(default)]
+    pub unattributed: usize,
+    /// the root namespace first - other namespaces only if enabled
+    #[serde(default)]
+    pub namespaces: Vec<Namespace>,
+}
+
+/// Outcome of collecting a machine.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    #[default]
+    Ok,
+    /// some parts are missing - see the errors
+    Partial,
+    /// no session could be opened or it timed out
+    Unreachable,
+}
+
+/// A network namespace with its own interfaces and sockets.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Namespace {
+    /// `root`, the `ip netns` name or `net:[inode]` for unnamed namespaces (containers)
+    pub name: String,
+    pub inode: Option<u64>,
     pub interfaces: Vec<Interface>,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+    #[serde(default)]
+    pub neighbors: Vec<Neighbor>,
+    /// none if no rules could be read - the listeners are not evaluated then
+    #[serde(default)]
+    pub firewall: Option<Firewall>,
     pub processes: Vec<Process>,
+    #[serde(default)]
+    pub unix_sockets: Vec<UnixSocket>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Interface {
     pub name: String,
     pub addresses: Vec<String>,
+    /// operational state, e.g. `UP`, `DOWN` or `UNKNOWN`
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub mac: Option<String>,
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    /// e.g. `ether` or `loopback`
+    #[serde(default)]
+    pub link_type: Option<String>,
+    /// kind of virtual links, e.g. `veth`, `bridge` or `vlan`
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// ifindex - unique within the namespace only
+    #[serde(default)]
+    pub index: Option<u32>,
+    /// bridge or bond the link is enslaved to
+    #[serde(default)]
+    pub master: Option<String>,
+    /// parent (vlan, macvlan) or veth peer in the same namespace
+    #[serde(default)]
+    pub link: Option<String>,
+    /// ifindex of the parent or veth peer in another namespace
+    #[serde(default)]
+    pub link_index: Option<u32>,
+    /// id of that namespace as seen from this one
+    #[serde(default)]
+    pub link_netnsid: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Interface {
+    /// The link has no carrier or is disabled - `UNKNOWN` links (loopback, tunnels) are up.
+    pub fn is_down(&self) -> bool {
+        matches!(self.state.as_deref(), Some("DOWN" | "LOWERLAYERDOWN"))
+    }
+}
+
+/// A route of any routing table - `ip route show table all`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    /// `default` or a network, e.g. `10.0.0.0/24`
+    pub destination: String,
+    pub gateway: Option<String>,
+    pub device: Option<String>,
+    pub metric: Option<u32>,
+    pub table: String,
+    /// blackhole, unreachable, prohibit, ... - none for unicast routes
+    pub kind: Option<String>,
+}
+
+/// A policy routing rule - `ip rule show`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutingRule {
+    pub priority: Option<u32>,
+    /// selector and action, e.g. `from 10.0.0.0/8 lookup 100`
+    pub rule: String,
+}
+
+/// An entry of the neighbor table - a host on the same segment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Neighbor {
+    pub address: String,
+    /// link layer address - none if the host did not answer
+    pub mac: Option<String>,
+    pub device: Option<String>,
+    /// e.g. `REACHABLE`, `STALE` or `FAILED`
+    pub state: String,
+}
+
+/// Filter rules for incoming connections - rules with conditions the evaluation cannot model
+/// (negations, named sets, connection states other than new, loopback only) are left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Firewall {
+    /// `nftables` or `iptables`
+    pub source: String,
+    pub chains: Vec<FilterChain>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterChain {
+    /// `ip`, `ip6` or `inet` (both)
+    pub family: String,
+    pub table: String,
+    pub name: String,
+    /// hooked into the input path - other chains are only jumped to
+    pub input: bool,
+    /// input chains are passed in the order of their priority
+    pub priority: i32,
+    /// verdict if no rule decides - input chains only
+    pub policy: Option<Verdict>,
+    pub rules: Vec<FilterRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterRule {
+    /// `tcp` or `udp` - any protocol if none
+    pub protocol: Option<String>,
+    /// source networks - any source if empty
+    pub sources: Vec<String>,
+    /// destination port ranges - any port if empty
+    pub ports: Vec<(u16, u16)>,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Accept,
+    /// drop or reject
+    Drop,
+    Return,
+    /// jump to a chain of the same table
+    Jump(String),
+    /// goto a chain of the same table - its end returns to the calling chain of this one
+    Goto(String),
+}
+
+/// Whether new connections to a listener pass the firewall.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reachability {
+    /// from any source
+    Open,
+    /// from these source networks only - `!10.0.0.0/8` excludes a network, only excluded
+    /// networks allow any other source
+    Restricted(Vec<String>),
+    Firewalled,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Process {
     pub name: String,
+    #[serde(default)]
+    pub pid: Option<u32>,
+    #[serde(default)]
+    pub ppid: Option<u32>,
+    #[serde(default)]
+    pub uid: Option<u32>,
+    #[serde(default)]
+    pub user: Option<String>,
+    /// target of `/proc/<pid>/exe`
+    #[serde(default)]
+    pub exe: Option<String>,
+    #[serde(default)]
+    pub cmdline: Option<String>,
+    /// seconds since the epoch
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    #[serde(default)]
+    pub container: Option<Container>,
+    /// systemd unit, e.g. `postgresql.service`
+    #[serde(default)]
+    pub unit: Option<String>,
     pub addresses: Vec<String>,
+    /// reachability of the addresses - loopback addresses and hosts without firewall rules are
+    /// not evaluated
+    #[serde(default)]
+    pub reachability: BTreeMap<String, Reachability>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Container {
+    pub id: String,
+    /// docker, podman, containerd or cri-o - guessed from the cgroup path
+    pub runtime: String,
+    pub name: Option<String>,
+    pub image: Option<String>,
+}
+
+/// A process holding a socket open.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SocketUser {
+    pub name: String,
+    pub pid: Option<u32>,
+    pub fd: Option<u32>,
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+/// A unix domain socket - a listener or one end of a connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnixSocket {
+    /// `u_str`, `u_dgr` or `u_seq`
+    pub kind: String,
+    pub state: String,
+    /// file system path or `@name` for abstract sockets - none for unnamed sockets
+    pub path: Option<String>,
+    pub inode: u64,
+    /// inode of the other end - none for listeners
+    pub peer: Option<u64>,
+    pub users: Vec<SocketUser>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Connection {
     pub host: String,
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
     pub process: String,
+    /// all processes sharing the socket - `process` is the name of the first one
+    #[serde(default)]
+    pub users: Vec<SocketUser>,
     pub local_addr: String,
     pub local_port: String,
     pub remote_addr: String,
     pub remote_port: String,
+    /// `ss` notation, e.g. `ESTAB`, `SYN-SENT` or `TIME-WAIT`
+    #[serde(default = "default_state")]
+    pub state: String,
+}
+
+/// A host to collect - the local machine is collected without ssh, remote hosts optionally
+/// through jump hosts.
+#[derive(Debug, Clone)]
+enum Target {
+    Local,
+    Remote { host: String, jump: Vec<String> },
+}
+
+fn transport_for(target: &Target, settings: &Settings) -> Box<dyn Transport> {
+    match target {
+        Target::Local => settings.transports.local(),
+        Target::Remote { host, jump } => settings.transports.for_host(host, jump),
+    }
+}
+
+/// Record of a host whose session failed - named by its address.
+fn unreachable(target: &Target, hostname: Option<String>, error: CollectError) -> Machine {
+    let address = match target {
+        Target::Local => None,
+        Target::Remote { host, .. } => Some(host.to_string()),
+    };
+    let hostname = hostname
+        .or_else(|| address.clone())
+        .unwrap_or_else(|| dns_lookup::get_hostname().unwrap_or_default());
+    log::warn!("Cannot collect {}: {}", hostname, error);
+    Machine {
+        hostname,
+        address,
+        jump: vec![],
+        status: Status::Unreachable,
+        errors: vec![error.to_string()],
+        unattributed: 0,
+        namespaces: vec![],
+    }
+}
+
+/// Collect a host in one session - a second session collects the other namespaces if enabled.
+///
+/// The host is checked against networks and excludes before and after its hostname is known.
+/// The hostname comes with the host script, an excluded hostname is dropped after the session.
+/// A host reached via the networks is kept under its address if its hostname is not in them.
+/// The local machine is always collected, the networks only limit which remote hosts are
+/// crawled from it.
+fn collect(target: &Target, settings: &Settings) -> Option<(Machine, Vec<Connection>)> {
+    let (excludes, networks) = (&settings.excludes, &settings.networks);
+    if let Target::Remote { host, .. } = target {
+        if !is_host_in_network(host, networks) || is_host_excluded(host, excludes) {
+            return None;
+        }
+    }
+    let transport = transport_for(target, settings);
+    let transport = transport.as_ref();
+    let privileged = |script: String| {
+        if settings.sudo {
+            sudo_script(&script)
+        } else {
+            script
+        }
+    };
+    let session = |error| CollectError::Transport {
+        context: String::from("session"),
+        error,
+    };
+    let sections = match run_script(
+        transport,
+        &privileged(host_script(settings.collector, settings.namespaces)),
+    ) {
+        Ok(sections) => sections,
+        Err(error) => return Some((unreachable(target, None, session(error)), vec![])),
+    };
+    let mut errors: Vec<String> = vec![];
+    let reported = section(&sections, "hostname").trim().to_string();
+    let hostname = match target {
+        Target::Local => dns_lookup::get_hostname().unwrap_or(reported),
+        Target::Remote { host, .. } if reported.is_empty() => {
+            errors.push(String::from("hostname: no output - using the address"));
+            host.to_string()
+        }
+        Target::Remote { host, .. } if !is_host_in_network(&reported, networks) => {
+            errors.push(format!(
+                "hostname: {} is not in the networks - using the address",
+                reported
+            ));
+            host.to_string()
+        }
+        Target::Remote { .. } => reported,
+    };
+    if is_host_excluded(&hostname, excludes) {
+        return None;
+    }
+    if settings.sudo && sections.contains_key("nosudo") {
+        errors.push(String::from(
+            "sudo: not permitted without password - collected as login user",
+        ));
+    }
+    let host = match target {
+        Target::Local => hostname.as_str(),
+        Target::Remote { host, .. } => host.as_str(),
+    };
+    let netnses = if settings.namespaces {
+        let netnses = parse_namespaces(&sections);
+        log::info!(
+            "Found network namespaces on {}: {:?}",
+            host,
+            netnses.iter().map(|ns| &ns.name).collect::<Vec<_>>()
+        );
+        netnses
+    } else {
+        vec![NetNs {
+            name: String::from(ROOT),
+            inode: None,
+            prefix: None,
+        }]
+    };
+    let nested = if netnses.iter().any(|ns| ns.prefix.is_some()) {
+        run_script(
+            transport,
+            &privileged(namespaces_script(&netnses, settings.collector)),
+        )
+        .unwrap_or_else(|error| {
+            let error = CollectError::Transport {
+                context: String::from("namespaces"),
+                error,
+            };
+            log::warn!("Cannot collect {}: {}", host, error);
+            errors.push(error.to_string());
+            HashMap::new()
+        })
+    } else {
+        HashMap::new()
+    };
+    let mut namespaces = vec![];
+    let mut connections = vec![];
+    for netns in netnses {
+        let mut failed = vec![];
+        let nested_sections;
+        let namespace_sections = match netns.prefix {
+            Some(_) => {
+                nested_sections = subsections(&nested, &netns.section_prefix());
+                &nested_sections
+            }
+            None => &sections,
+        };
+        let (namespace, found) = parse_namespace(
+            namespace_sections,
+            &netns,
+            host,
+            excludes,
+            settings.collector,
+            &settings.states,
+            &mut failed,
+        );
+        for error in failed {
+            log::warn!("Incomplete {} namespace {}: {}", host, netns.name, error);
+            errors.push(match netns.prefix {
+                Some(_) => format!("{} {}", netns.name, error),
+                None => error.to_string(),
+            });
+        }
+        namespaces.push(namespace);
+        connections.extend(found);
+    }
+    add_process_info(&sections, &mut namespaces, &mut connections);
+    let unattributed = count_unattributed(&namespaces, &connections);
+    if unattributed > 0 && section(&sections, "uid").trim() != "0" {
+        log::warn!(
+            "{} sockets on {} without process - only root sees the owners, see --sudo",
+            unattributed,
+            host
+        );
+    }
+    let (address, jump) = match target {
+        Target::Local => (None, vec![]),
+        Target::Remote { host, jump } => (Some(host.to_string()), jump.to_vec()),
+    };
+    let machine = Machine {
+        hostname,
+        address,
+        jump,
+        status: if errors.is_empty() {
+            Status::Ok
+        } else {
+            Status::Partial
+        },
+        errors,
+        unattributed,
+        namespaces,
+    };
+    Some((machine, connections))
+}
+
+/// Listening sockets and connections whose process is unknown - sockets in TIME-WAIT belong to
+/// the kernel only.
+fn count_unattributed(namespaces: &[Namespace], connections: &[Connection]) -> usize {
+    let listening: usize = namespaces
+        .iter()
+        .flat_map(|ns| &ns.processes)
+        .filter(|process| process.pid.is_none() && process.name.is_empty())
+        .map(|process| process.addresses.len())
+        .sum();
+    listening
+        + connections
+            .iter()
+            .filter(|connection| connection.users.is_empty() && connection.state != "TIME-WAIT")
+            .count()
+}
+
+/// Move the `interfaces` and `processes` of a machine of a model written before namespaces were
+/// collected into its root namespace.
+fn migrate_machine(machine: &mut Value) {
+    let machine = match machine.as_object_mut() {
+        Some(machine) if !machine.contains_key("namespaces") => machine,
+        _ => return,
+    };
+    let interfaces = machine.remove("interfaces").unwrap_or_else(|| json!([]));
+    let processes = machine.remove("processes").unwrap_or_else(|| json!([]));
+    machine.insert(
+        String::from("namespaces"),
+        json!([{ "name": ROOT, "interfaces": interfaces, "processes": processes }]),
+    );
+}
+
+fn default_namespace() -> String {
+    String::from(ROOT)
+}
+
+fn default_state() -> String {
+    String::from("ESTAB")
 }
 
 impl Model {
@@ -48,37 +529,125 @@ impl Model {
             connections: vec![],
         }
     }
-    pub fn add_machine(&mut self, host: &String, excludes: &Option<String>, networks: &String) {
-        if !is_host_in_network(&host, &networks) || is_host_excluded(&host, &excludes) {
-            return;
+    /// Read a model written by a previous run - older models are migrated.
+    pub fn load(filename: &str) -> Result<Model, String> {
+        let content = std::fs::read_to_string(filename)
+            .map_err(|err| format!("Cannot read model {}: {}", filename, err))?;
+        let mut value: Value = serde_json::from_str(&content)
+            .map_err(|err| format!("Cannot parse model {}: {}", filename, err))?;
+        if let Some(machines) = value["machines"].as_array_mut() {
+            machines.iter_mut().for_each(migrate_machine);
         }
-        let hostname = get_hostname(&host);
-        if !is_host_in_network(&hostname, &networks) || is_host_excluded(&hostname, &excludes) {
-            return;
+        serde_json::from_value(value)
+            .map_err(|err| format!("Cannot parse model {}: {}", filename, err))
+    }
+    /// Collect the hosts and crawl on to the remote hosts of their connections and, with
+    /// `settings.neighbors`, their neighbors.
+    ///
+    /// The hosts are collected in waves of up to `settings.jobs` hosts in parallel. The results
+    /// of a wave are merged in the order the hosts were queued, so the machines, and the address
+    /// kept for a host reached through several addresses, are the same on every run.
+    ///
+    /// With `settings.chain` a host is reached through the host whose connection led to it, the
+    /// initial hosts through the jump hosts of the previous model.
+    pub fn crawl(&mut self, hosts: &[String], local: bool, settings: &Settings) {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut targets = vec![];
+        if local {
+            targets.push(Target::Local);
         }
-        for item in &self.machines {
-            if item.hostname == hostname {
-                return;
+        for host in hosts {
+            if seen.insert(host.to_string()) {
+                let jump = match settings.jumps.get(host) {
+                    Some(jump) if settings.chain => jump.to_vec(),
+                    _ => vec![],
+                };
+                targets.push(Target::Remote {
+                    host: host.to_string(),
+                    jump,
+                });
             }
         }
-        let interfaces = get_interfaces(&host, &excludes);
-        let processes = get_processes(&host, &excludes);
-        self.machines.push(Machine {
-            hostname,
-            interfaces,
-            processes,
-        });
-        let mut connections = get_connections(&host, &excludes);
-        // TODO: add step to move connection addresses to existing interfaces
-        self.connections.append(&mut connections);
-        // TODO: call self.add_machine() with all remote hosts
-        // using connection src/dest
-        for remote_host in &connections {
-            self.add_machine(&remote_host.remote_addr, &excludes, &networks);
+        // a host reached through several addresses is only merged once
+        let mut known: HashSet<String> = HashSet::new();
+        while !targets.is_empty() {
+            log::info!("Crawling {} host(s)", targets.len());
+            let results = pool::run(settings.jobs, targets.clone(), |target| {
+                collect(&target, settings)
+            });
+            let mut next_targets = vec![];
+            for (target, collected) in targets.into_iter().zip(results) {
+                let (machine, connections) = match collected {
+                    Some(Some(collected)) => collected,
+                    Some(None) => continue,
+                    None => {
+                        let host = match target {
+                            Target::Local => String::from("the local machine"),
+                            Target::Remote { host, .. } => host,
+                        };
+                        log::error!("Cannot collect {} - collecting it panicked", host);
+                        continue;
+                    }
+                };
+                if !known.insert(machine.hostname.to_string()) {
+                    log::debug!("Skipping {} - already known", machine.hostname);
+                    continue;
+                }
+                let jump = match &machine.address {
+                    Some(address) if settings.chain => {
+                        settings.transports.jump_chain(address, &machine.jump)
+                    }
+                    _ => vec![],
+                };
+                let mut next = vec![];
+                // TODO: add step to move connection addresses to existing interfaces
+                // closing or half open connections may point to hosts that are gone
+                for connection in connections.iter().filter(|c| c.state == "ESTAB") {
+                    let host = unbracket(&connection.remote_addr);
+                    if seen.insert(host.to_string()) {
+                        next.push(host);
+                    }
+                }
+                // hosts on the same segment are crawled even without connections
+                let neighbors = machine
+                    .namespaces
+                    .iter()
+                    .flat_map(|ns| &ns.neighbors)
+                    .filter(|neighbor| is_reachable(neighbor) && !is_link_local(&neighbor.address));
+                if settings.neighbors {
+                    for neighbor in neighbors {
+                        if seen.insert(neighbor.address.to_string()) {
+                            next.push(neighbor.address.to_string());
+                        }
+                    }
+                }
+                if !next.is_empty() {
+                    log::info!("Found {} host(s) on {}", next.len(), machine.hostname);
+                }
+                next_targets.extend(next.into_iter().map(|host| Target::Remote {
+                    host,
+                    jump: jump.to_vec(),
+                }));
+                self.machines.push(machine);
+                self.connections.extend(connections);
+            }
+            targets = next_targets;
         }
     }
-    pub fn generate(&self, filename: &Option<String>) {
-        let output = generate_graph(&self);
+    /// Jump hosts of the remote machines by address - to reach them the same way on a rescan.
+    pub fn jumps(&self) -> HashMap<String, Vec<String>> {
+        self.machines
+            .iter()
+            .filter_map(|machine| {
+                machine
+                    .address
+                    .as_ref()
+                    .map(|address| (address.to_string(), machine.jump.to_vec()))
+            })
+            .collect()
+    }
+    pub fn generate(&self, filename: &Option<String>, options: &GraphOptions) {
+        let output = generate_graph(self, options);
         if let Some(filename) = filename {
             let mut file = File::create(filename).unwrap();
             file.write_all(output.as_bytes()).unwrap();
@@ -87,3 +656,25 @@ impl Model {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_time_wait_without_process() {
+        let connection = |state: &str, users: Value| {
+            json!({ "host": "host", "process": "", "users": users, "local_addr": "10.0.0.5",
+                    "local_port": "40022", "remote_addr": "10.0.0.7", "remote_port": "5432",
+                    "state": state })
+        };
+        let connections: Vec<Connection> = serde_json::from_value(json!([
+            connection("ESTAB", json!([])),
+            connection("ESTAB", json!([{ "name": "psql", "pid": 42, "fd": 3 }])),
+            connection("CLOSE-WAIT", json!([])),
+            connection("TIME-WAIT", json!([])),
+        ]))
+        .unwrap();
+        assert_eq!(count_unattributed(&[], &connections), 2);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::ssh::{HostKeyPolicy, SshOptions, SshPolicy};
+use crate::transport::{check_status, CommandOutput, Limit, Transport, TransportError};
+use ssh2::{CheckResult, KnownHostFileKind, Session};
+use std::io::Read;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+
+/// Key files tried after the agent if no identity is given - as `ssh` does.
+const DEFAULT_IDENTITIES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Runs commands with the built-in ssh client - authenticates with the ssh agent and key files
+/// and checks the host key against `~/.ssh/known_hosts`.
+pub struct NativeTransport {
+    pub host: String,
+    pub options: SshOptions,
+    pub policy: SshPolicy,
+    pub limit: Limit,
+}
+
+fn ssh_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".ssh"))
+}
+
+impl NativeTransport {
+    fn connect(&self) -> Result<Session, TransportError> {
+        let port = self.options.port.unwrap_or(22);
+        let address = (self.host.as_str(), port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| TransportError::Connection(format!("cannot resolve {}", self.host)))?;
+        let stream = TcpStream::connect_timeout(&address, self.policy.connect_timeout)?;
+        let mut session = Session::new().map_err(|err| self.error(err))?;
+        session.set_tcp_stream(stream);
+        session.set_timeout(self.policy.connect_timeout.as_millis() as u32);
+        session.handshake().map_err(|err| self.error(err))?;
+        self.check_host_key(&session, port)?;
+        self.authenticate(&session)?;
+        // the limit applies to each blocking call, not the whole command - 0 waits forever and is
+        // only used without limit
+        let timeout = match self.limit.remaining() {
+            None => 0,
+            Some(timeout) if timeout.is_zero() => {
+                return Err(TransportError::Timeout(format!(
+                    "{}: host timeout exceeded",
+                    self.host
+                )))
+            }
+            Some(timeout) => timeout.as_millis().clamp(1, u32::MAX as u128) as u32,
+        };
+        session.set_timeout(timeout);
+        Ok(session)
+    }
+    fn check_host_key(&self, session: &Session, port: u16) -> Result<(), TransportError> {
+        if self.policy.host_key == HostKeyPolicy::Ignore {
+            return Ok(());
+        }
+        let (key, key_type) = session
+            .host_key()
+            .ok_or_else(|| TransportError::HostKey(format!("{}: no host key", self.host)))?;
+        let mut known_hosts = session.known_hosts().map_err(|err| self.error(err))?;
+        let file = ssh_dir().map(|dir| dir.join("known_hosts"));
+        if let Some(file) = &file {
+            // a missing file only means that no host is known
+            let _ = known_hosts.read_file(file, KnownHostFileKind::OpenSSH);
+        }
+        match known_hosts.check_port(&self.host, port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(TransportError::HostKey(format!(
+                "{}: key differs from known_hosts",
+                self.host
+            ))),
+            CheckResult::NotFound if self.policy.host_key == HostKeyPolicy::AcceptNew => {
+                log::warn!("Adding the key of {} to known_hosts", self.host);
+                let name = match port {
+                    22 => self.host.to_string(),
+                    _ => format!("[{}]:{}", self.host, port),
+                };
+                known_hosts
+                    .add(&name, key, "", key_type.into())
+                    .map_err(|err| self.error(err))?;
+                if let Some(file) = &file {
+                    known_hosts
+                        .write_file(file, KnownHostFileKind::OpenSSH)
+                        .map_err(|err| self.error(err))?;
+                }
+                Ok(())
+            }
+            CheckResult::NotFound => Err(TransportError::HostKey(format!(
+                "{}: not in known_hosts",
+                self.host
+            ))),
+            CheckResult::Failure => Err(TransportError::HostKey(format!(
+                "{}: cannot check the key",
+                self.host
+            ))),
+        }
+    }
+    fn authenticate(&self, session: &Session) -> Result<(), TransportError> {
+        let user = match &self.options.user {
+            Some(user) => user.to_string(),
+            None => std::env::var("USER").unwrap_or_else(|_| String::from("root")),
+        };
+        if session.userauth_agent(&user).is_ok() {
+            return Ok(());
+        }
+        let identities: Vec<PathBuf> = match &self.options.identity {
+            Some(identity) => vec![PathBuf::from(identity)],
+            None => ssh_dir()
+                .map(|dir| DEFAULT_IDENTITIES.iter().map(|id| dir.join(id)).collect())
+                .unwrap_or_default(),
+        };
+        for identity in identities.iter().filter(|identity| identity.exists()) {
+            if session
+                .userauth_pubkey_file(&user, None, identity, None)
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+        Err(TransportError::Auth(format!(
+            "{}@{}: neither agent nor key files accepted",
+            user, self.host
+        )))
+    }
+    fn error(&self, err: ssh2::Error) -> TransportError {
+        match TransportError::from(std::io::Error::from(err)) {
+            TransportError::Io(err) => {
+                TransportError::Connection(format!("{}: {}", self.host, err))
+            }
+            err => err,
+        }
+    }
+}
+
+impl Transport for NativeTransport {
+    fn run(&self, command: &str) -> Result<CommandOutput, TransportError> {
+        log::debug!("Native ssh {}: {:?}", self.host, command);
+        let session = self.connect()?;
+        let mut channel = session.channel_session().map_err(|err| self.error(err))?;
+        channel.exec(command).map_err(|err| self.error(err))?;
+        let mut stdout = vec![];
+        channel.read_to_end(&mut stdout)?;
+        let mut stderr = vec![];
+        channel.stderr().read_to_end(&mut stderr)?;
+        channel.wait_close().map_err(|err| self.error(err))?;
+        let status = channel.exit_status().map_err(|err| self.error(err))?;
+        check_status(CommandOutput {
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            status: Some(status),
+        })
+    }
+}
@@ -0,0 +1,115 @@
+use crate::exclude::is_device_excluded;
+use crate::model::Neighbor;
+use std::net::IpAddr;
+
+/// Neighbor (ARP and NDP) table - IPv4 and IPv6.
+pub const SECTIONS: [(&str, &str); 1] = [("neighbors", "ip neigh show")];
+
+/// States of entries whose host did not answer.
+const UNREACHABLE_STATES: [&str; 2] = ["FAILED", "INCOMPLETE"];
+
+/// Parse `ip neigh show`, e.g. `10.0.0.1 dev eth0 lladdr 52:54:00:12:34:56 router REACHABLE`.
+pub fn parse_neighbors(content: &str, host: &str, excludes: &Option<String>) -> Vec<Neighbor> {
+    let mut neighbors: Vec<Neighbor> = vec![];
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let address = match fields.first() {
+            Some(address) => address.to_string(),
+            None => continue,
+        };
+        let mut neighbor = Neighbor {
+            address,
+            mac: None,
+            device: None,
+            state: String::new(),
+        };
+        let mut fields = fields[1..].iter();
+        while let Some(field) = fields.next() {
+            match *field {
+                "dev" => neighbor.device = fields.next().map(|value| value.to_string()),
+                "lladdr" => neighbor.mac = fields.next().map(|value| value.to_string()),
+                // flags, e.g. `router` or `proxy`
+                field if field.chars().all(|c| c.is_ascii_lowercase()) => {}
+                // several states are joined, e.g. `STALE` or `REACHABLE PERMANENT`
+                state => {
+                    if !neighbor.state.is_empty() {
+                        neighbor.state.push(' ');
+                    }
+                    neighbor.state.push_str(state);
+                }
+            }
+        }
+        if let Some(device) = &neighbor.device {
+            if is_device_excluded(host, device, excludes) {
+                continue;
+            }
+        }
+        if !neighbors.contains(&neighbor) {
+            neighbors.push(neighbor);
+        }
+    }
+    neighbors
+}
+
+/// IPv6 `fe80::/10` and IPv4 `169.254.0.0/16` addresses - only usable with the interface.
+pub fn is_link_local(address: &str) -> bool {
+    match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_link_local(),
+        Ok(IpAddr::V6(ip)) => ip.segments()[0] & 0xffc0 == 0xfe80,
+        Err(_) => false,
+    }
+}
+
+/// The neighbor answered recently enough to have a link layer address.
+pub fn is_reachable(neighbor: &Neighbor) -> bool {
+    neighbor.mac.is_some()
+        && !neighbor
+            .state
+            .split(' ')
+            .any(|state| UNREACHABLE_STATES.contains(&state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ip neigh show` of iproute2 6.1.
+    const NEIGHBORS: &str = "\
+10.0.0.1 dev eth0 lladdr 52:54:00:12:34:56 router REACHABLE
+10.0.0.7 dev eth0 lladdr 52:54:00:ab:cd:ef STALE
+10.0.0.9 dev eth0 FAILED
+10.0.0.10 dev eth0 INCOMPLETE
+fe80::1 dev eth0 lladdr 52:54:00:12:34:56 router STALE
+2001:db8::9 dev eth0 lladdr 52:54:00:ab:cd:ef REACHABLE
+";
+
+    #[test]
+    fn parses_neighbors() {
+        let neighbors = parse_neighbors(NEIGHBORS, "host", &None);
+        assert_eq!(neighbors.len(), 6);
+        assert_eq!(neighbors[0].address, "10.0.0.1");
+        assert_eq!(neighbors[0].device.as_deref(), Some("eth0"));
+        assert_eq!(neighbors[0].mac.as_deref(), Some("52:54:00:12:34:56"));
+        assert_eq!(neighbors[0].state, "REACHABLE");
+        let reachable: Vec<&str> = neighbors
+            .iter()
+            .filter(|neighbor| is_reachable(neighbor))
+            .map(|neighbor| neighbor.address.as_str())
+            .collect();
+        assert_eq!(
+            reachable,
+            ["10.0.0.1", "10.0.0.7", "fe80::1", "2001:db8::9"]
+        );
+    }
+
+    #[test]
+    fn detects_link_local() {
+        assert!(is_link_local("fe80::1"));
+        assert!(is_link_local("febf::1"));
+        assert!(is_link_local("169.254.169.254"));
+        assert!(!is_link_local("fec0::1"));
+        assert!(!is_link_local("2001:db8::9"));
+        assert!(!is_link_local("10.0.0.1"));
+        assert!(!is_link_local("gateway"));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::cli::section;
+use crate::transport::shell_quote;
+use std::collections::{BTreeMap, HashMap};
+
+pub const ROOT: &str = "root";
+
+pub const SECTIONS: [(&str, &str); 3] = [
+    ("self", "readlink /proc/self/ns/net"),
+    (
+        "named",
+        "for ns in $(ip netns list | cut -d' ' -f1); do echo $ns $(stat -L -c %i /run/netns/$ns); done",
+    ),
+    (
+        "pids",
+        "for p in /proc/[0-9]*; do echo ${p#/proc/} $(readlink $p/ns/net); done",
+    ),
+];
+
+/// A network namespace found on a host and how to enter it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetNs {
+    /// `root` for the namespace the transport lands in, the `ip netns` name or `net:[inode]`
+    pub name: String,
+    pub inode: Option<u64>,
+    /// command prefix to run a command inside the namespace - none for the root namespace
+    pub prefix: Option<String>,
+}
+
+impl NetNs {
+    /// Prefix of the section names of this namespace in `cli::namespaces_script`.
+    pub fn section_prefix(&self) -> String {
+        format!("{}/", self.name)
+    }
+}
+
+/// Parse a `net:[4026531840]` link as printed by `readlink /proc/<pid>/ns/net`.
+fn parse_inode(link: &str) -> Option<u64> {
+    link.trim()
+        .strip_prefix("net:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+/// Build the namespace list from the `SECTIONS` output - the root namespace first, then the
+/// named namespaces and the unnamed namespaces of processes (containers) ordered by inode.
+pub fn parse_namespaces(sections: &HashMap<String, String>) -> Vec<NetNs> {
+    let root = parse_inode(section(sections, "self"));
+    let mut namespaces = vec![NetNs {
+        name: String::from(ROOT),
+        inode: root,
+        prefix: None,
+    }];
+    for line in section(sections, "named").lines() {
+        let mut fields = line.split_whitespace();
+        if let Some(name) = fields.next() {
+            let inode = fields.next().and_then(|inode| inode.parse().ok());
+            if inode.is_some() && inode == root {
+                continue;
+            }
+            namespaces.push(NetNs {
+                name: name.to_string(),
+                inode,
+                prefix: Some(format!("ip netns exec {}", shell_quote(name))),
+            });
+        }
+    }
+    let mut unnamed: BTreeMap<u64, u32> = BTreeMap::new();
+    for line in section(sections, "pids").lines() {
+        let mut fields = line.split_whitespace();
+        let pid = fields.next().and_then(|pid| pid.parse().ok());
+        let inode = fields.next().and_then(parse_inode);
+        if let (Some(pid), Some(inode)) = (pid, inode) {
+            if Some(inode) != root && !namespaces.iter().any(|ns| ns.inode == Some(inode)) {
+                unnamed.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    for (inode, pid) in unnamed {
+        namespaces.push(NetNs {
+            name: format!("net:[{}]", inode),
+            inode: Some(inode),
+            prefix: Some(format!("nsenter -t {} -n", pid)),
+        });
+    }
+    namespaces
+}
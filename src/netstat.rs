@@ -0,0 +1,172 @@
+use crate::cli::Socket;
+use crate::model::SocketUser;
+use crate::network::normalize_address;
+
+/// Map the net-tools/lsof state names to the `ss` notation.
+pub fn state_name(state: &str) -> String {
+    let name = match state {
+        "ESTABLISHED" => "ESTAB",
+        "SYN_SENT" => "SYN-SENT",
+        "SYN_RECV" => "SYN-RECV",
+        "FIN_WAIT1" => "FIN-WAIT-1",
+        "FIN_WAIT2" => "FIN-WAIT-2",
+        "TIME_WAIT" => "TIME-WAIT",
+        "CLOSE_WAIT" => "CLOSE-WAIT",
+        "LAST_ACK" => "LAST-ACK",
+        "IDLE" => "UNCONN",
+        other => other,
+    };
+    name.to_string()
+}
+
+/// Split `addr:port` and normalize the address (`:::22` -> `[::]`, `22`).
+pub fn split_endpoint(endpoint: &str) -> Option<(String, String)> {
+    let (addr, port) = endpoint.rsplit_once(':')?;
+    Some((normalize_address(addr), port.to_string()))
+}
+
+fn is_process_field(field: &str) -> bool {
+    field == "-" || field.contains('/')
+}
+
+/// Parse the output of `netstat -tuapnW` (net-tools and busybox).
+pub fn parse_sockets(content: &str) -> Vec<Socket> {
+    let mut sockets = vec![];
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 || !(fields[0].starts_with("tcp") || fields[0].starts_with("udp")) {
+            continue;
+        }
+        let (local, remote) = match (split_endpoint(fields[3]), split_endpoint(fields[4])) {
+            (Some(local), Some(remote)) => (local, remote),
+            _ => {
+                log::warn!("Cannot parse netstat line: {}", line);
+                continue;
+            }
+        };
+        let protocol = fields[0].trim_end_matches('6').to_string();
+        // UDP sockets without a peer have an empty state column
+        let (state, rest) = match fields.get(5) {
+            Some(field) if !is_process_field(field) => (state_name(field), &fields[6..]),
+            _ if remote.1 == "*" => (String::from("UNCONN"), &fields[5..]),
+            _ => (String::from("ESTAB"), &fields[5..]),
+        };
+        // PID/Program name - `-` if the process is not visible
+        let users = match rest.join(" ").split_once('/') {
+            Some((pid, program)) => vec![SocketUser {
+                name: program
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or("")
+                    .trim_end_matches(':')
+                    .to_string(),
+                pid: pid.parse().ok(),
+                fd: None,
+                unit: None,
+            }],
+            None => vec![],
+        };
+        sockets.push(Socket {
+            protocol,
+            state,
+            local_addr: local.0,
+            local_port: local.1,
+            remote_addr: remote.0,
+            remote_port: remote.1,
+            users,
+        });
+    }
+    sockets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `netstat -tuapnW` of net-tools 2.10 - wide addresses, program names with arguments.
+    const NET_TOOLS: &str = "\
+Active Internet connections (servers and established)
+Proto Recv-Q Send-Q Local Address           Foreign Address         State       PID/Program name    
+tcp        0      0 0.0.0.0:22              0.0.0.0:*               LISTEN      812/sshd: /usr/sbin 
+tcp        0      0 10.0.0.5:22             10.0.0.9:51234          ESTABLISHED 1404/sshd: alice [pr 
+tcp        0      1 10.0.0.5:40022          10.0.0.7:5432           SYN_SENT    2001/psql           
+tcp6       0      0 :::22                   :::*                    LISTEN      812/sshd: /usr/sbin 
+tcp6       0      0 2001:db8::5:443         2001:db8::9:40112       TIME_WAIT   -                   
+udp        0      0 0.0.0.0:68              0.0.0.0:*                           655/dhclient        
+udp        0      0 10.0.0.5:123            10.0.0.1:123            ESTABLISHED 700/chronyd         
+udp6       0      0 fe80::5054:ff:fe12:3456%eth0:546 :::*                       -                   
+";
+
+    /// `netstat -tuapn` of busybox 1.36 - no `-W`, same columns.
+    const BUSYBOX: &str = "\
+Active Internet connections (servers and established)
+Proto Recv-Q Send-Q Local Address           Foreign Address         State       PID/Program name
+tcp        0      0 0.0.0.0:80              0.0.0.0:*               LISTEN      1/nginx: master pro
+tcp        0      0 172.17.0.2:80           172.17.0.1:39812        ESTABLISHED 7/nginx: worker pro
+udp        0      0 127.0.0.11:53           0.0.0.0:*                           -
+";
+
+    #[test]
+    fn parses_net_tools() {
+        let sockets = parse_sockets(NET_TOOLS);
+        assert_eq!(sockets.len(), 8);
+        assert_eq!(sockets[0].to_string(), "tcp LISTEN 0.0.0.0:22 0.0.0.0:*");
+        assert_eq!(sockets[0].users[0].name, "sshd");
+        assert_eq!(sockets[0].users[0].pid, Some(812));
+        assert_eq!(
+            sockets[1].to_string(),
+            "tcp ESTAB 10.0.0.5:22 10.0.0.9:51234"
+        );
+        assert_eq!(sockets[2].state, "SYN-SENT");
+        assert_eq!(sockets[2].users[0].name, "psql");
+        assert_eq!(sockets[3].to_string(), "tcp LISTEN [::]:22 [::]:*");
+        assert_eq!(
+            sockets[4].to_string(),
+            "tcp TIME-WAIT [2001:db8::5]:443 [2001:db8::9]:40112"
+        );
+        assert!(sockets[4].users.is_empty());
+    }
+
+    #[test]
+    fn udp_without_state() {
+        let sockets = parse_sockets(NET_TOOLS);
+        assert_eq!(sockets[5].to_string(), "udp UNCONN 0.0.0.0:68 0.0.0.0:*");
+        assert_eq!(sockets[5].users[0].name, "dhclient");
+        assert_eq!(sockets[5].users[0].pid, Some(655));
+        assert_eq!(
+            sockets[6].to_string(),
+            "udp ESTAB 10.0.0.5:123 10.0.0.1:123"
+        );
+        assert_eq!(
+            sockets[7].to_string(),
+            "udp UNCONN [fe80::5054:ff:fe12:3456%eth0]:546 [::]:*"
+        );
+        assert!(sockets[7].users.is_empty());
+    }
+
+    #[test]
+    fn parses_busybox() {
+        let sockets = parse_sockets(BUSYBOX);
+        assert_eq!(sockets.len(), 3);
+        assert_eq!(sockets[0].users[0].name, "nginx");
+        assert_eq!(sockets[0].users[0].pid, Some(1));
+        assert_eq!(
+            sockets[1].to_string(),
+            "tcp ESTAB 172.17.0.2:80 172.17.0.1:39812"
+        );
+        assert_eq!(sockets[2].to_string(), "udp UNCONN 127.0.0.11:53 0.0.0.0:*");
+        assert!(sockets[2].users.is_empty());
+    }
+
+    #[test]
+    fn maps_state_names() {
+        assert_eq!(state_name("ESTABLISHED"), "ESTAB");
+        assert_eq!(state_name("SYN_RECV"), "SYN-RECV");
+        assert_eq!(state_name("FIN_WAIT1"), "FIN-WAIT-1");
+        assert_eq!(state_name("FIN_WAIT2"), "FIN-WAIT-2");
+        assert_eq!(state_name("CLOSE_WAIT"), "CLOSE-WAIT");
+        assert_eq!(state_name("LAST_ACK"), "LAST-ACK");
+        assert_eq!(state_name("IDLE"), "UNCONN");
+        assert_eq!(state_name("LISTEN"), "LISTEN");
+    }
+}
@@ -4,9 +4,9 @@ use std::net::IpAddr;
 
 fn is_valid_hostname(hostname: &str) -> bool {
     fn is_valid_char(byte: u8) -> bool {
-        (byte >= b'a' && byte <= b'z')
-            || (byte >= b'A' && byte <= b'Z')
-            || (byte >= b'0' && byte <= b'9')
+        byte.is_ascii_lowercase()
+            || byte.is_ascii_uppercase()
+            || byte.is_ascii_digit()
             || byte == b'-'
             || byte == b'.'
     }
@@ -19,12 +19,33 @@ fn is_valid_hostname(hostname: &str) -> bool {
         || hostname.is_empty())
 }
 
-fn get_ip_addresses(host: &str) -> Vec<IpAddr> {
+/// An address in the notation shared by all collectors - IPv6 addresses in brackets, wildcards
+/// as `0.0.0.0` or `[::]`. The `*` of ss (dual stack sockets, any wildcard of old versions) is
+/// taken as `[::]`.
+pub fn normalize_address(addr: &str) -> String {
+    match addr {
+        "*" => String::from("[::]"),
+        addr if addr.contains(':') && !addr.starts_with('[') => format!("[{}]", addr),
+        addr => addr.to_string(),
+    }
+}
+
+/// Remove the brackets of an IPv6 address in `ss` notation, e.g. `[2001:db8::1]` - ssh expects
+/// plain addresses as host.
+pub fn unbracket(addr: &str) -> String {
+    addr.replace(['[', ']'], "")
+}
+
+/// IP addresses of a host name or address - empty if the name cannot be resolved.
+pub fn resolve(host: &str) -> Vec<IpAddr> {
     let mut ips: Vec<IpAddr> = vec![];
-    log::debug!("{} is valid hostname?: {}", host, is_valid_hostname(&host));
-    if is_valid_hostname(&host) {
+    log::debug!("{} is valid hostname?: {}", host, is_valid_hostname(host));
+    if is_valid_hostname(host) {
         log::debug!("Found hostname: {}", host);
-        ips = lookup_host(&host).expect("Cannot obtain IP address for host");
+        match lookup_host(host) {
+            Ok(found) => ips = found,
+            Err(err) => log::warn!("Cannot obtain IP address for host {}: {}", host, err),
+        }
     } else {
         log::debug!("Found IP address: {}", host);
         if let Ok(ip) = host.parse() {
@@ -34,13 +55,17 @@ fn get_ip_addresses(host: &str) -> Vec<IpAddr> {
     ips
 }
 
-pub fn is_host_in_network(host: &str, networks: &String) -> bool {
+pub fn is_host_in_network(host: &str, networks: &str) -> bool {
+    is_ip_in_network(host, &resolve(host), networks)
+}
+
+/// Whether one of the addresses `ips` of `host` is in one of the comma separated `networks`.
+pub fn is_ip_in_network(host: &str, ips: &[IpAddr], networks: &str) -> bool {
     let mut valid = false;
-    let ips: Vec<std::net::IpAddr> = get_ip_addresses(host);
     log::debug!("Check if {:?} in networks {}", ips, networks);
     'outer: for network in networks.split(",") {
         if let Ok(nw) = IPAddress::parse(network) {
-            for ip in &ips {
+            for ip in ips {
                 if let Ok(ip) = IPAddress::parse(ip.to_string()) {
                     if nw.includes(&ip) {
                         valid = true;
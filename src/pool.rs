@@ -0,0 +1,77 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+
+/// Apply `f` to all items using at most `jobs` threads.
+///
+/// The results keep the order of the items, whichever finishes first - none if `f` panicked.
+pub fn run<T, R, F>(jobs: usize, items: Vec<T>, f: F) -> Vec<Option<R>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let count = items.len();
+    let (queue, jobs_receiver) = mpsc::channel::<(usize, T)>();
+    for item in items.into_iter().enumerate() {
+        queue.send(item).unwrap();
+    }
+    // workers stop once the queue is empty
+    drop(queue);
+    let jobs_receiver = Mutex::new(jobs_receiver);
+    let (results, results_receiver) = mpsc::channel::<(usize, Option<R>)>();
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, count.max(1)) {
+            let (jobs_receiver, results, f) = (&jobs_receiver, results.clone(), &f);
+            scope.spawn(move || loop {
+                let next = jobs_receiver.lock().unwrap().recv();
+                let (index, item) = match next {
+                    Ok(next) => next,
+                    Err(_) => break,
+                };
+                let result = panic::catch_unwind(AssertUnwindSafe(|| f(item))).ok();
+                if results.send((index, result)).is_err() {
+                    break;
+                }
+            });
+        }
+    });
+    drop(results);
+    let mut ordered: Vec<Option<R>> = (0..count).map(|_| None).collect();
+    for (index, result) in results_receiver {
+        ordered[index] = result;
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn keeps_the_order_of_the_items() {
+        // the first items finish last
+        let results = run(4, vec![40, 30, 20, 10, 0], |millis: u64| {
+            thread::sleep(Duration::from_millis(millis));
+            millis
+        });
+        assert_eq!(
+            results,
+            vec![Some(40), Some(30), Some(20), Some(10), Some(0)]
+        );
+    }
+
+    #[test]
+    fn survives_panics() {
+        let results = run(2, vec![1, 2, 3], |item: u32| {
+            if item == 2 {
+                panic!("item {}", item);
+            }
+            item
+        });
+        assert_eq!(results, vec![Some(1), None, Some(3)]);
+        assert!(run(2, vec![], |item: u32| item).is_empty());
+    }
+}
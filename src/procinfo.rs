@@ -0,0 +1,192 @@
+use crate::cli::section;
+use std::collections::HashMap;
+
+/// Sections collected after the socket tables - the details of the processes holding sockets,
+/// one block per pid (see `parse_blocks`), and the accounts of their uids. The uids are looked
+/// up as `getent passwd` does not list directory (LDAP, SSSD) users.
+pub const SECTIONS: [(&str, &str); 4] = [
+    ("btime", "grep '^btime' /proc/stat"),
+    ("clk_tck", "getconf CLK_TCK"),
+    (
+        "procinfo",
+        r#"for d in /proc/[0-9]*; do ls -l $d/fd 2>/dev/null | grep -q 'socket:\[' || continue; echo pid ${d#/proc/}; printf 'stat %s\n' "$(cat $d/stat)"; grep '^Uid:' $d/status; printf 'exe %s\n' "$(readlink $d/exe)"; printf 'cmdline %s\n' "$(tr '\000\n' '  ' < $d/cmdline)"; sed 's/^/cgroup /' $d/cgroup; done"#,
+    ),
+    (
+        "passwd",
+        "getent passwd $(awk '/^Uid:/ { print $2 }' /proc/[0-9]*/status | sort -u) || cat /etc/passwd",
+    ),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct ProcInfo {
+    pub ppid: Option<u32>,
+    pub uid: Option<u32>,
+    pub user: Option<String>,
+    pub exe: Option<String>,
+    pub cmdline: Option<String>,
+    /// seconds since the epoch
+    pub start_time: Option<u64>,
+    /// lines of `/proc/<pid>/cgroup`
+    pub cgroups: Vec<String>,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Parse a `/proc/<pid>/stat` line - returns the parent pid and the start time in clock ticks
+/// since boot.
+///
+/// The comm field is in parentheses and may contain spaces, the fields are counted from the
+/// last closing parenthesis: `pid (comm) state ppid ... starttime ...`.
+fn parse_stat(line: &str) -> (Option<u32>, Option<u64>) {
+    let rest = match line.rfind(')') {
+        Some(index) => &line[index + 1..],
+        None => return (None, None),
+    };
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    (
+        fields.get(1).and_then(|ppid| ppid.parse().ok()),
+        fields.get(19).and_then(|start| start.parse().ok()),
+    )
+}
+
+/// Parse `getent passwd` or `/etc/passwd` - returns the user name of each uid.
+pub fn parse_passwd(content: &str) -> HashMap<u32, String> {
+    let mut users = HashMap::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        if let (Some(name), Some(Ok(uid))) = (fields.first(), fields.get(2).map(|uid| uid.parse()))
+        {
+            users.entry(uid).or_insert_with(|| name.to_string());
+        }
+    }
+    users
+}
+
+/// Parse the `procinfo` section - blocks of `pid`, `stat`, `Uid:`, `exe`, `cmdline` and
+/// `cgroup` lines.
+///
+/// `btime` is the boot time in seconds since the epoch and `clk_tck` the clock ticks per second
+/// used to convert the start time.
+pub fn parse_blocks(
+    content: &str,
+    btime: Option<u64>,
+    clk_tck: u64,
+    users: &HashMap<u32, String>,
+) -> HashMap<u32, ProcInfo> {
+    let mut infos: HashMap<u32, ProcInfo> = HashMap::new();
+    let mut current: Option<u32> = None;
+    for line in content.lines() {
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if key == "pid" {
+            current = value.trim().parse().ok();
+            continue;
+        }
+        let info = match current {
+            Some(pid) => infos.entry(pid).or_default(),
+            None => continue,
+        };
+        match key {
+            "stat" => {
+                let (ppid, start) = parse_stat(value);
+                info.ppid = ppid;
+                info.start_time = match (btime, start) {
+                    (Some(btime), Some(start)) => Some(btime + start / clk_tck),
+                    _ => None,
+                };
+            }
+            "Uid:" => {
+                info.uid = value
+                    .split_whitespace()
+                    .next()
+                    .and_then(|uid| uid.parse().ok());
+                info.user = info.uid.and_then(|uid| users.get(&uid).cloned());
+            }
+            "exe" => info.exe = non_empty(value),
+            "cmdline" => info.cmdline = non_empty(value),
+            "cgroup" => info.cgroups.push(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    infos
+}
+
+pub fn parse_sections(sections: &HashMap<String, String>) -> HashMap<u32, ProcInfo> {
+    let btime = section(sections, "btime")
+        .split_whitespace()
+        .nth(1)
+        .and_then(|btime| btime.parse().ok());
+    let clk_tck = section(sections, "clk_tck")
+        .trim()
+        .parse()
+        .ok()
+        .filter(|clk_tck| *clk_tck > 0)
+        .unwrap_or(100);
+    let users = parse_passwd(section(sections, "passwd"));
+    parse_blocks(section(sections, "procinfo"), btime, clk_tck, &users)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of the `procinfo` section - the comm of the second process contains spaces and
+    /// parentheses.
+    const PROCINFO: &str = "\
+pid 1034
+stat 1034 (sshd) S 1 1034 1034 0 -1 4194560 144 0 0 0 83 89 0 0 20 0 1 0 355 12963840 1161
+Uid:\t0\t0\t0\t0
+exe /usr/sbin/sshd
+cmdline sshd: /usr/sbin/sshd -D [listener] 0 of 10-100 startups 
+cgroup 0::/system.slice/ssh.service
+pid 4200
+stat 4200 (my (odd) app) S 1034 4200 4200 0 -1 4194304 10 0 0 0 1 1 0 0 20 0 1 0 35500 1000 100
+Uid:\t1000\t1000\t1000\t1000
+exe 
+cmdline 
+cgroup 0::/user.slice/user-1000.slice/session-3.scope
+";
+
+    const PASSWD: &str = "\
+root:x:0:0:root:/root:/bin/bash
+alice:x:1000:1000:Alice:/home/alice:/bin/bash
+";
+
+    #[test]
+    fn parses_process_blocks() {
+        let sections = HashMap::from([
+            (String::from("btime"), String::from("btime 1700000000\n")),
+            (String::from("clk_tck"), String::from("100\n")),
+            (String::from("procinfo"), PROCINFO.to_string()),
+            (String::from("passwd"), PASSWD.to_string()),
+        ]);
+        let infos = parse_sections(&sections);
+        let sshd = &infos[&1034];
+        assert_eq!(sshd.ppid, Some(1));
+        assert_eq!(sshd.start_time, Some(1700000003));
+        assert_eq!(sshd.user.as_deref(), Some("root"));
+        assert_eq!(sshd.exe.as_deref(), Some("/usr/sbin/sshd"));
+        assert_eq!(sshd.cgroups, vec!["0::/system.slice/ssh.service"]);
+        let app = &infos[&4200];
+        assert_eq!(app.ppid, Some(1034));
+        assert_eq!(app.start_time, Some(1700000355));
+        assert_eq!((app.uid, app.user.as_deref()), (Some(1000), Some("alice")));
+        // kernel threads and processes of other users without permission
+        assert_eq!((app.exe.as_ref(), app.cmdline.as_ref()), (None, None));
+    }
+
+    #[test]
+    fn parses_stat_without_times() {
+        let sections = HashMap::from([(String::from("procinfo"), PROCINFO.to_string())]);
+        let infos = parse_sections(&sections);
+        assert_eq!(infos[&1034].start_time, None);
+        assert_eq!(infos[&1034].user, None);
+        assert_eq!(parse_stat("garbage"), (None, None));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::cli::{section, Socket};
+use crate::model::SocketUser;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+const SOCKET_TABLES: [&str; 4] = ["tcp", "tcp6", "udp", "udp6"];
+
+pub const SECTIONS: [(&str, &str); 6] = [
+    ("tcp", "cat /proc/net/tcp"),
+    ("tcp6", "cat /proc/net/tcp6"),
+    ("udp", "cat /proc/net/udp"),
+    ("udp6", "cat /proc/net/udp6"),
+    ("fd", "ls -l /proc/[0-9]*/fd"),
+    ("comm", "grep -H '' /proc/[0-9]*/comm"),
+];
+
+/// Map the kernel socket state (`include/net/tcp_states.h`) to the `ss` notation.
+fn state_name(state: &str, protocol: &str) -> String {
+    let name = match state {
+        "01" => "ESTAB",
+        "02" => "SYN-SENT",
+        "03" => "SYN-RECV",
+        "04" => "FIN-WAIT-1",
+        "05" => "FIN-WAIT-2",
+        "06" => "TIME-WAIT",
+        "07" if protocol == "udp" => "UNCONN",
+        "07" => "CLOSE",
+        "08" => "CLOSE-WAIT",
+        "09" => "LAST-ACK",
+        "0A" => "LISTEN",
+        "0B" => "CLOSING",
+        _ => "UNKNOWN",
+    };
+    name.to_string()
+}
+
+/// Decode a `/proc/net` address - the kernel prints each 32 bit word of the address in host
+/// byte order, the port in network byte order.
+///
+/// The remote host is assumed to be little endian (x86, arm64) - the addresses of big endian
+/// hosts (s390x, ppc64) come out byte swapped.
+fn parse_endpoint(endpoint: &str) -> Option<(String, String)> {
+    let (addr, port) = endpoint.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?.to_string();
+    let mut words = vec![];
+    for index in (0..addr.len()).step_by(8) {
+        words.push(
+            u32::from_str_radix(addr.get(index..index + 8)?, 16)
+                .ok()?
+                .swap_bytes(),
+        );
+    }
+    let addr = match words[..] {
+        [word] => Ipv4Addr::from(word).to_string(),
+        [a, b, c, d] => {
+            let ip = Ipv6Addr::from(
+                (u128::from(a) << 96)
+                    | (u128::from(b) << 64)
+                    | (u128::from(c) << 32)
+                    | u128::from(d),
+            );
+            match ip.to_ipv4_mapped() {
+                Some(ipv4) => format!("[::ffff:{}]", ipv4),
+                None => format!("[{}]", ip),
+            }
+        }
+        _ => return None,
+    };
+    Some((addr, port))
+}
+
+/// Parse the content of `/proc/net/{tcp,tcp6,udp,udp6}` - returns sockets and their inodes.
+pub fn parse_socket_table(content: &str, protocol: &str) -> Vec<(Socket, String)> {
+    let mut sockets = vec![];
+    for line in content.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            log::debug!("Skipping short /proc/net line: {}", line);
+            continue;
+        }
+        let (local, remote) = match (parse_endpoint(fields[1]), parse_endpoint(fields[2])) {
+            (Some(local), Some(remote)) => (local, remote),
+            _ => {
+                log::warn!("Cannot parse /proc/net line: {}", line);
+                continue;
+            }
+        };
+        let socket = Socket {
+            protocol: protocol.to_string(),
+            state: state_name(fields[3], protocol),
+            local_addr: local.0,
+            local_port: local.1,
+            remote_addr: remote.0,
+            remote_port: remote.1,
+            users: vec![],
+        };
+        sockets.push((socket, fields[9].to_string()));
+    }
+    sockets
+}
+
+/// Parse `ls -l /proc/[0-9]*/fd` - returns the pids and fds holding each socket inode.
+pub fn parse_socket_owners(content: &str) -> HashMap<String, Vec<(u32, Option<u32>)>> {
+    let mut owners: HashMap<String, Vec<(u32, Option<u32>)>> = HashMap::new();
+    let mut pid: Option<u32> = None;
+    for line in content.lines() {
+        if let Some(dir) = line.strip_suffix("/fd:") {
+            pid = dir.trim_start_matches("/proc/").parse().ok();
+        } else if let (Some(pid), Some((link, target))) = (pid, line.split_once(" -> socket:[")) {
+            let fd = link
+                .split_whitespace()
+                .last()
+                .and_then(|fd| fd.parse().ok());
+            owners
+                .entry(target.trim_end_matches(']').to_string())
+                .or_default()
+                .push((pid, fd));
+        }
+    }
+    owners
+}
+
+/// Parse `grep -H '' /proc/[0-9]*/comm` - returns the process name of each pid.
+pub fn parse_process_names(content: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for line in content.lines() {
+        if let Some((path, name)) = line.split_once(':') {
+            let pid = path
+                .trim_start_matches("/proc/")
+                .trim_end_matches("/comm")
+                .parse();
+            if let Ok(pid) = pid {
+                names.insert(pid, name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Build the sockets from the `SECTIONS` output.
+pub fn parse_sockets(sections: &HashMap<String, String>) -> Vec<Socket> {
+    let owners = parse_socket_owners(section(sections, "fd"));
+    let names = parse_process_names(section(sections, "comm"));
+    let mut sockets = vec![];
+    for table in SOCKET_TABLES {
+        let protocol = table.trim_end_matches('6');
+        for (mut socket, inode) in parse_socket_table(section(sections, table), protocol) {
+            for (pid, fd) in owners.get(&inode).into_iter().flatten() {
+                socket.users.push(SocketUser {
+                    name: names.get(pid).cloned().unwrap_or_default(),
+                    pid: Some(*pid),
+                    fd: *fd,
+                    unit: None,
+                });
+            }
+            sockets.push(socket);
+        }
+    }
+    sockets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 21050 1 0000000000000000 100 0 0 10 0
+   1: 0500000A:0016 0900000A:C862 01 00000000:00000000 02:000A7E6C 00000000     0        0 38211 4 0000000000000000 20 4 29 10 -1
+   2: 0100007F:1538 0100007F:9C40 06 00000000:00000000 03:00001770 00000000     0        0 0 3 0000000000000000
+";
+
+    const TCP6: &str = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000000000000:0016 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 21051 1 0000000000000000 100 0 0 10 0
+   1: B80D0120000000000000000005000000:0016 B80D0120000000000000000009000000:9CB0 01 00000000:00000000 02:00094F2E 00000000     0        0 38300 2 0000000000000000 20 4 30 10 -1
+   2: 0000000000000000FFFF00000500000A:1F90 0000000000000000FFFF00000900000A:D6D8 01 00000000:00000000 00:00000000 00000000  1000        0 38301 1 0000000000000000 20 4 30 10 -1
+";
+
+    const FD: &str = "\
+/proc/1034/fd:
+total 0
+lr-x------ 1 root root 64 Jan  1 00:00 0 -> /dev/null
+lrwx------ 1 root root 64 Jan  1 00:00 3 -> socket:[21050]
+
+/proc/1404/fd:
+lrwx------ 1 root root 64 Jan  1 00:00 4 -> socket:[38211]
+";
+
+    #[test]
+    fn parses_ipv4_lines() {
+        let sockets = parse_socket_table(TCP, "tcp");
+        assert_eq!(sockets.len(), 3);
+        assert_eq!(sockets[0].0.to_string(), "tcp LISTEN 0.0.0.0:22 0.0.0.0:0");
+        assert_eq!(sockets[0].1, "21050");
+        assert_eq!(
+            sockets[1].0.to_string(),
+            "tcp ESTAB 10.0.0.5:22 10.0.0.9:51298"
+        );
+        assert_eq!(
+            sockets[2].0.to_string(),
+            "tcp TIME-WAIT 127.0.0.1:5432 127.0.0.1:40000"
+        );
+    }
+
+    #[test]
+    fn swaps_ipv6_words() {
+        let sockets = parse_socket_table(TCP6, "tcp");
+        assert_eq!(sockets[0].0.to_string(), "tcp LISTEN [::]:22 [::]:0");
+        assert_eq!(
+            sockets[1].0.to_string(),
+            "tcp ESTAB [2001:db8::5]:22 [2001:db8::9]:40112"
+        );
+        assert_eq!(
+            sockets[2].0.to_string(),
+            "tcp ESTAB [::ffff:10.0.0.5]:8080 [::ffff:10.0.0.9]:55000"
+        );
+        assert_eq!(parse_endpoint("0100007F"), None);
+        assert_eq!(parse_endpoint("0100:0016"), None);
+    }
+
+    #[test]
+    fn maps_state_names() {
+        assert_eq!(state_name("0A", "tcp"), "LISTEN");
+        assert_eq!(state_name("07", "udp"), "UNCONN");
+        assert_eq!(state_name("07", "tcp"), "CLOSE");
+        assert_eq!(state_name("08", "tcp"), "CLOSE-WAIT");
+        assert_eq!(state_name("FF", "tcp"), "UNKNOWN");
+    }
+
+    #[test]
+    fn finds_socket_owners() {
+        let sections = HashMap::from([
+            (String::from("tcp"), TCP.to_string()),
+            (String::from("fd"), FD.to_string()),
+            (
+                String::from("comm"),
+                String::from("/proc/1034/comm:sshd\n/proc/1404/comm:sshd\n"),
+            ),
+        ]);
+        let sockets = parse_sockets(&sections);
+        assert_eq!(sockets.len(), 3);
+        assert_eq!(sockets[0].users[0].name, "sshd");
+        assert_eq!(sockets[0].users[0].pid, Some(1034));
+        assert_eq!(sockets[0].users[0].fd, Some(3));
+        assert_eq!(sockets[1].users[0].pid, Some(1404));
+        assert!(sockets[2].users.is_empty());
+    }
+}
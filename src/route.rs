@@ -0,0 +1,208 @@
+use crate::cli::section;
+use crate::model::{Route, RoutingRule};
+use std::collections::HashMap;
+
+/// Routes of all tables and the policy rules selecting the tables - IPv4 and IPv6.
+pub const SECTIONS: [(&str, &str); 2] = [
+    (
+        "routes",
+        "ip -4 route show table all; ip -6 route show table all",
+    ),
+    ("rules", "ip -4 rule show; ip -6 rule show"),
+];
+
+/// Route types only used for traffic to the host itself.
+const LOCAL_TYPES: [&str; 4] = ["local", "broadcast", "anycast", "multicast"];
+
+/// Route types without next hop.
+const REJECT_TYPES: [&str; 5] = ["blackhole", "unreachable", "prohibit", "throw", "nat"];
+
+/// Read the `via`, `dev`, `metric` and `table` keywords of a route or nexthop line.
+fn parse_keywords(route: &mut Route, fields: &[&str]) {
+    let mut fields = fields.iter();
+    while let Some(field) = fields.next() {
+        match *field {
+            "via" => route.gateway = fields.next().map(|value| value.to_string()),
+            "dev" => route.device = fields.next().map(|value| value.to_string()),
+            "metric" => route.metric = fields.next().and_then(|value| value.parse().ok()),
+            "table" => {
+                if let Some(table) = fields.next() {
+                    route.table = table.to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse `ip route show table all`, e.g. `default via 10.0.0.1 dev eth0 proto dhcp metric 100`.
+///
+/// Routes to the host itself (table local) are skipped. Multipath routes become one route per
+/// `nexthop` line.
+pub fn parse_routes(content: &str) -> Vec<Route> {
+    let mut routes: Vec<Route> = vec![];
+    let mut multipath: Option<Route> = None;
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            if let (Some(route), "nexthop") = (&multipath, fields[0]) {
+                let mut hop = route.clone();
+                parse_keywords(&mut hop, &fields[1..]);
+                routes.push(hop);
+            }
+            continue;
+        }
+        multipath = None;
+        let (kind, rest) = if LOCAL_TYPES.contains(&fields[0]) || REJECT_TYPES.contains(&fields[0])
+        {
+            (Some(fields[0]), &fields[1..])
+        } else {
+            (None, &fields[..])
+        };
+        if kind.is_some_and(|kind| LOCAL_TYPES.contains(&kind)) {
+            continue;
+        }
+        let destination = match rest.first() {
+            Some(destination) => destination.to_string(),
+            None => continue,
+        };
+        let mut route = Route {
+            destination,
+            gateway: None,
+            device: None,
+            metric: None,
+            table: String::from("main"),
+            kind: kind.map(|kind| kind.to_string()),
+        };
+        parse_keywords(&mut route, &rest[1..]);
+        if route.gateway.is_none() && route.device.is_none() && route.kind.is_none() {
+            // the next hops follow on their own lines
+            multipath = Some(route);
+        } else if !routes.contains(&route) {
+            routes.push(route);
+        }
+    }
+    routes
+}
+
+/// Parse `ip rule show`, e.g. `32766: from all lookup main`.
+pub fn parse_rules(content: &str) -> Vec<RoutingRule> {
+    let mut rules: Vec<RoutingRule> = vec![];
+    for line in content.lines() {
+        if let Some((priority, rule)) = line.split_once(':') {
+            let rule = RoutingRule {
+                priority: priority.trim().parse().ok(),
+                rule: rule.split_whitespace().collect::<Vec<_>>().join(" "),
+            };
+            if !rule.rule.is_empty() && !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+    }
+    rules
+}
+
+/// Routes and rules of the `SECTIONS` output.
+pub fn parse_sections(sections: &HashMap<String, String>) -> (Vec<Route>, Vec<RoutingRule>) {
+    (
+        parse_routes(section(sections, "routes")),
+        parse_rules(section(sections, "rules")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ip -4 route show table all; ip -6 route show table all` - a policy routing table, a
+    /// multipath route and the local table.
+    const ROUTES: &str = "\
+default via 192.168.1.1 dev wg0 table 100
+default proto static metric 50
+\tnexthop via 10.0.0.1 dev eth0 weight 1
+\tnexthop via 10.0.1.1 dev eth1 weight 1
+default via 10.0.0.1 dev eth0 proto dhcp src 10.0.0.5 metric 100
+10.0.0.0/24 dev eth0 proto kernel scope link src 10.0.0.5 metric 100
+blackhole 10.99.0.0/16 proto static
+local 10.0.0.5 dev eth0 table local proto kernel scope host src 10.0.0.5
+broadcast 10.0.0.255 dev eth0 table local proto kernel scope link src 10.0.0.5
+fe80::/64 dev eth0 proto kernel metric 256 pref medium
+fe80::/64 dev eth0 proto kernel metric 256 pref medium
+local ::1 dev lo table local proto kernel metric 0 pref medium
+";
+
+    const RULES: &str = "\
+0:\tfrom all lookup local
+32765:\tfrom 10.1.0.0/16 lookup 100
+32766:\tfrom all lookup main
+32767:\tfrom all lookup default
+0:\tfrom all lookup local
+32766:\tfrom all lookup main
+";
+
+    fn route(destination: &str, gateway: Option<&str>, device: Option<&str>) -> Route {
+        Route {
+            destination: destination.to_string(),
+            gateway: gateway.map(String::from),
+            device: device.map(String::from),
+            metric: None,
+            table: String::from("main"),
+            kind: None,
+        }
+    }
+
+    #[test]
+    fn parses_routes() {
+        let routes = parse_routes(ROUTES);
+        assert_eq!(routes.len(), 7);
+        assert_eq!(
+            routes[0],
+            Route {
+                table: String::from("100"),
+                ..route("default", Some("192.168.1.1"), Some("wg0"))
+            }
+        );
+        assert_eq!(
+            routes[3],
+            Route {
+                metric: Some(100),
+                ..route("default", Some("10.0.0.1"), Some("eth0"))
+            }
+        );
+        assert_eq!(routes[4].device.as_deref(), Some("eth0"));
+        assert_eq!(
+            routes[5],
+            Route {
+                kind: Some(String::from("blackhole")),
+                ..route("10.99.0.0/16", None, None)
+            }
+        );
+        // the duplicate IPv6 route and the local table are skipped
+        assert_eq!(routes[6].destination, "fe80::/64");
+    }
+
+    #[test]
+    fn splits_multipath_routes() {
+        let routes = parse_routes(ROUTES);
+        assert_eq!(
+            routes[1],
+            Route {
+                metric: Some(50),
+                ..route("default", Some("10.0.0.1"), Some("eth0"))
+            }
+        );
+        assert_eq!(routes[2].gateway.as_deref(), Some("10.0.1.1"));
+        assert_eq!(routes[2].device.as_deref(), Some("eth1"));
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rules = parse_rules(RULES);
+        assert_eq!(rules.len(), 4);
+        assert_eq!(rules[1].priority, Some(32765));
+        assert_eq!(rules[1].rule, "from 10.1.0.0/16 lookup 100");
+    }
+}
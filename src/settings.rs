@@ -0,0 +1,50 @@
+use crate::cli::{Collector, States};
+use crate::ssh::SshRule;
+use crate::transport::Transports;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+/// Options controlling how hosts are crawled and collected.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub excludes: Option<String>,
+    pub networks: String,
+    pub transports: Transports,
+    pub collector: Collector,
+    /// states of the connections kept - listeners are always kept
+    pub states: States,
+    /// collect all network namespaces instead of the root namespace only
+    pub namespaces: bool,
+    /// crawl the reachable hosts of the neighbor tables
+    pub neighbors: bool,
+    /// number of hosts collected in parallel
+    pub jobs: usize,
+    /// reach discovered hosts through the host that saw the connection
+    pub chain: bool,
+    /// run the commands with `sudo -n`
+    pub sudo: bool,
+    /// jump hosts by address from the previous model - used for the initial hosts with `chain`
+    pub jumps: HashMap<String, Vec<String>>,
+}
+
+/// Settings read from the `--config` file (JSON), e.g.
+///
+/// ```json
+/// {"ssh": [{"user": "admin"}, {"network": "10.20.0.0/16", "jump": "bastion"}]}
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// ssh rules - applied before the rules given on the command line
+    #[serde(default)]
+    pub ssh: Vec<SshRule>,
+}
+
+impl Config {
+    pub fn load(filename: &str) -> Result<Config, String> {
+        let content = fs::read_to_string(filename)
+            .map_err(|err| format!("cannot read config file '{}': {}", filename, err))?;
+        serde_json::from_str(&content)
+            .map_err(|err| format!("invalid config file '{}': {}", filename, err))
+    }
+}
@@ -0,0 +1,246 @@
+use crate::cli::Socket;
+use crate::model::SocketUser;
+use crate::network::normalize_address;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// no `State ... Local Address:Port Peer Address:Port` header line found
+    MissingHeader,
+    /// the header lacks a mandatory column
+    MissingColumn(&'static str),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingHeader => write!(f, "no ss header line found"),
+            ParseError::MissingColumn(column) => write!(f, "ss header lacks column '{}'", column),
+        }
+    }
+}
+
+/// Column positions derived from the ss header line.
+struct Columns {
+    netid: Option<usize>,
+    state: usize,
+    local: usize,
+    peer: usize,
+    /// first column of the trailing `Process`/extended info, which may contain spaces
+    rest: usize,
+}
+
+impl Columns {
+    fn from_header(line: &str) -> Result<Columns, ParseError> {
+        // newer ss versions glue the Process column to the peer column: `Address:PortProcess`
+        let header = line
+            .replace("Local Address:Port", " Local ")
+            .replace("Peer Address:Port", " Peer ");
+        let names: Vec<&str> = header.split_whitespace().collect();
+        log::debug!("ss header columns: {:?}", names);
+        let position = |name: &'static str| -> Result<usize, ParseError> {
+            names
+                .iter()
+                .position(|column| *column == name)
+                .ok_or(ParseError::MissingColumn(name))
+        };
+        let peer = position("Peer")?;
+        Ok(Columns {
+            netid: position("Netid").ok(),
+            state: position("State")?,
+            local: position("Local")?,
+            peer,
+            rest: peer + 1,
+        })
+    }
+}
+
+fn is_header(line: &str) -> bool {
+    line.contains("State") && line.contains("Local Address:Port")
+}
+
+/// Split `addr:port` and normalize the address - `%dev` scopes are kept as printed by ss.
+fn split_endpoint(endpoint: &str) -> Option<(String, String)> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(?P<addr>.+):(?P<port>\d{1,5}|\*)$").unwrap();
+    }
+    let cap = RE.captures(endpoint)?;
+    Some((normalize_address(&cap["addr"]), cap["port"].to_string()))
+}
+
+/// All processes of a `users:(("name",pid=1,fd=3),("name",pid=2,fd=3))` field.
+pub fn extract_users(input: &str) -> Vec<SocketUser> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r#"\("(?P<name>[^"]*)"(,pid=(?P<pid>\d+))?(,fd=(?P<fd>\d+))?"#).unwrap();
+    }
+    RE.captures_iter(input)
+        .map(|cap| SocketUser {
+            name: cap["name"].to_string(),
+            pid: cap.name("pid").and_then(|pid| pid.as_str().parse().ok()),
+            fd: cap.name("fd").and_then(|fd| fd.as_str().parse().ok()),
+            unit: None,
+        })
+        .collect()
+}
+
+/// Parse the output of `ss -tuapn` and friends based on its header line.
+///
+/// The Netid column is optional (missing for `ss -t`), `protocol` is used in that case. Lines
+/// that cannot be parsed are logged and skipped.
+pub fn parse_sockets(content: &str, protocol: &str) -> Result<Vec<Socket>, ParseError> {
+    let mut lines = content.lines().skip_while(|line| !is_header(line));
+    let columns = match lines.next() {
+        Some(header) => Columns::from_header(header)?,
+        None if content.trim().is_empty() => return Ok(vec![]),
+        None => return Err(ParseError::MissingHeader),
+    };
+    let mut sockets = vec![];
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        if fields.len() < columns.rest {
+            log::warn!("Cannot parse ss line - too few columns: {}", line);
+            continue;
+        }
+        let (local, peer) = match (
+            split_endpoint(fields[columns.local]),
+            split_endpoint(fields[columns.peer]),
+        ) {
+            (Some(local), Some(peer)) => (local, peer),
+            _ => {
+                log::warn!("Cannot parse ss line - invalid address: {}", line);
+                continue;
+            }
+        };
+        let protocol = match columns.netid {
+            Some(index) => fields[index].to_string(),
+            None => protocol.to_string(),
+        };
+        sockets.push(Socket {
+            protocol,
+            state: fields[columns.state].to_string(),
+            local_addr: local.0,
+            local_port: local.1,
+            remote_addr: peer.0,
+            remote_port: peer.1,
+            users: extract_users(&fields[columns.rest..].join(" ")),
+        });
+    }
+    Ok(sockets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ss -tuapn` of iproute2-ss130716 (CentOS 7) - separate columns, unbracketed wildcards.
+    const OLD: &str = "\
+Netid  State      Recv-Q Send-Q     Local Address:Port       Peer Address:Port 
+udp    UNCONN     0      0                      *:68                    *:*      users:((\"dhclient\",pid=655,fd=6))
+tcp    LISTEN     0      128                    *:22                    *:*      users:((\"sshd\",pid=1034,fd=3))
+tcp    ESTAB      0      0               10.0.0.5:22             10.0.0.9:51234  users:((\"sshd\",pid=1404,fd=3),(\"sshd\",pid=1406,fd=3))
+tcp    LISTEN     0      128                   :::22                   :::*      users:((\"sshd\",pid=1034,fd=4))
+";
+
+    /// `ss -tuapn` of iproute2 6.1 (Debian 12) - Process glued to the peer column header.
+    const NEW: &str = "\
+Netid State  Recv-Q Send-Q                    Local Address:Port   Peer Address:PortProcess                                   
+udp   UNCONN 0      0                         127.0.0.53%lo:53          0.0.0.0:*    users:((\"systemd-resolve\",pid=600,fd=13))
+udp   ESTAB  0      0      [fe80::5054:ff:fe12:3456]%eth0:546 [fe80::1]%eth0:547    users:((\"dhclient\",pid=655,fd=7))
+tcp   LISTEN 0      4096                               [::]:22             [::]:*    users:((\"sshd\",pid=812,fd=4))
+tcp   ESTAB  0      0                       [2001:db8::5]:22   [2001:db8::9]:40112 users:((\"sshd\",pid=1404,fd=4),(\"sshd\",pid=1406,fd=4))
+tcp   TIME-WAIT 0   0                          10.0.0.5:40022      10.0.0.7:5432                                             
+";
+
+    /// `ss -tan` of iproute2 5.15 - no Netid and no Process column.
+    const NO_PROCESS: &str = "\
+State  Recv-Q Send-Q Local Address:Port  Peer Address:Port
+LISTEN 0      128          0.0.0.0:22         0.0.0.0:*
+ESTAB  0      0           10.0.0.5:22        10.0.0.9:51234
+";
+
+    #[test]
+    fn parses_old_iproute2() {
+        let sockets = parse_sockets(OLD, "tcp").unwrap();
+        assert_eq!(sockets.len(), 4);
+        assert_eq!(sockets[0].to_string(), "udp UNCONN [::]:68 [::]:*");
+        assert_eq!(sockets[1].to_string(), "tcp LISTEN [::]:22 [::]:*");
+        assert_eq!(
+            sockets[2].to_string(),
+            "tcp ESTAB 10.0.0.5:22 10.0.0.9:51234"
+        );
+        assert_eq!(sockets[3].to_string(), "tcp LISTEN [::]:22 [::]:*");
+        assert_eq!(sockets[3].users[0].fd, Some(4));
+    }
+
+    #[test]
+    fn parses_new_iproute2() {
+        let sockets = parse_sockets(NEW, "tcp").unwrap();
+        assert_eq!(sockets.len(), 5);
+        assert_eq!(
+            sockets[0].to_string(),
+            "udp UNCONN 127.0.0.53%lo:53 0.0.0.0:*"
+        );
+        assert_eq!(sockets[0].users[0].name, "systemd-resolve");
+        assert_eq!(
+            sockets[1].to_string(),
+            "udp ESTAB [fe80::5054:ff:fe12:3456]%eth0:546 [fe80::1]%eth0:547"
+        );
+        assert_eq!(sockets[2].to_string(), "tcp LISTEN [::]:22 [::]:*");
+        assert_eq!(
+            sockets[3].to_string(),
+            "tcp ESTAB [2001:db8::5]:22 [2001:db8::9]:40112"
+        );
+        assert_eq!(
+            sockets[4].to_string(),
+            "tcp TIME-WAIT 10.0.0.5:40022 10.0.0.7:5432"
+        );
+        assert!(sockets[4].users.is_empty());
+    }
+
+    #[test]
+    fn keeps_all_users() {
+        for content in [OLD, NEW] {
+            let sockets = parse_sockets(content, "tcp").unwrap();
+            let users = &sockets
+                .iter()
+                .find(|socket| socket.local_port == "22" && socket.state == "ESTAB")
+                .unwrap()
+                .users;
+            assert_eq!(users.len(), 2);
+            assert_eq!(users[0].name, "sshd");
+            assert_eq!(users[0].pid, Some(1404));
+            assert_eq!(users[1].pid, Some(1406));
+        }
+    }
+
+    #[test]
+    fn parses_without_netid_and_process() {
+        let sockets = parse_sockets(NO_PROCESS, "tcp").unwrap();
+        assert_eq!(sockets.len(), 2);
+        assert_eq!(sockets[0].to_string(), "tcp LISTEN 0.0.0.0:22 0.0.0.0:*");
+        assert_eq!(
+            sockets[1].to_string(),
+            "tcp ESTAB 10.0.0.5:22 10.0.0.9:51234"
+        );
+        assert!(sockets[1].users.is_empty());
+    }
+
+    #[test]
+    fn reports_missing_header() {
+        assert_eq!(parse_sockets("", "tcp").unwrap().len(), 0);
+        assert_eq!(
+            parse_sockets("ss: command not found\n", "tcp").unwrap_err(),
+            ParseError::MissingHeader
+        );
+        assert_eq!(
+            parse_sockets("State Recv-Q Send-Q Local Address:Port Remote\n", "tcp").unwrap_err(),
+            ParseError::MissingColumn("Peer")
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use crate::network::{is_ip_in_network, resolve};
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Key of an `-o` option - `Key=value` or `Key value`, ssh ignores the case of keys.
+fn option_key(option: &str) -> String {
+    option
+        .split(|c: char| c == '=' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Options passed to `ssh` - unset options are left to `~/.ssh/config`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SshOptions {
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// identity file (`-i`)
+    #[serde(default)]
+    pub identity: Option<String>,
+    /// jump host(s) (`-J`), e.g. `bastion` or `admin@bastion:2222`
+    #[serde(default)]
+    pub jump: Option<String>,
+    /// extra `-o` options, e.g. `Ciphers=aes256-ctr`
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+impl SshOptions {
+    /// Override the options set in `other` - `-o` options are added, replacing earlier values
+    /// of the same key as ssh keeps the first one.
+    fn merge(&mut self, other: &SshOptions) {
+        if other.user.is_some() {
+            self.user = other.user.clone();
+        }
+        if other.port.is_some() {
+            self.port = other.port;
+        }
+        if other.identity.is_some() {
+            self.identity = other.identity.clone();
+        }
+        if other.jump.is_some() {
+            self.jump = other.jump.clone();
+        }
+        self.options.retain(|option| {
+            !other
+                .options
+                .iter()
+                .any(|o| option_key(o) == option_key(option))
+        });
+        self.options.extend(other.options.iter().cloned());
+    }
+    /// Arguments for the `ssh` command line.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(user) = &self.user {
+            args.extend([String::from("-l"), user.to_string()]);
+        }
+        if let Some(port) = self.port {
+            args.extend([String::from("-p"), port.to_string()]);
+        }
+        if let Some(identity) = &self.identity {
+            args.extend([String::from("-i"), identity.to_string()]);
+        }
+        if let Some(jump) = &self.jump {
+            args.extend([String::from("-J"), jump.to_string()]);
+        }
+        for option in &self.options {
+            args.extend([String::from("-o"), option.to_string()]);
+        }
+        args
+    }
+}
+
+/// SSH options for the hosts matching a host name or network - rules without host and network
+/// apply to all hosts.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SshRule {
+    /// host as given on the command line or found as remote address of a connection
+    #[serde(default)]
+    pub host: Option<String>,
+    /// network in CIDR format, e.g. `10.20.0.0/16`
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(flatten)]
+    pub options: SshOptions,
+}
+
+impl SshRule {
+    /// `ips` are the addresses of `host` - resolved once for all rules.
+    fn matches(&self, host: &str, ips: &[IpAddr]) -> bool {
+        if let Some(name) = &self.host {
+            if name != host {
+                return false;
+            }
+        }
+        match &self.network {
+            Some(network) => is_ip_in_network(host, ips, network),
+            None => true,
+        }
+    }
+}
+
+/// Split a rule at commas - values containing commas are quoted, e.g.
+/// `option="Ciphers=aes128-ctr,aes256-ctr"`.
+fn split_fields(input: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+impl FromStr for SshRule {
+    type Err = String;
+
+    /// Parse `HOST|NETWORK,KEY=VALUE[,KEY=VALUE...]` - a target containing `/` is a network.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let fields = split_fields(input);
+        let mut fields = fields.iter();
+        let target = fields.next().map_or("", |target| target.as_str());
+        if target.is_empty() || target.contains('=') {
+            return Err(format!(
+                "invalid ssh rule '{}' - expected HOST|NETWORK,KEY=VALUE[,KEY=VALUE...]",
+                input
+            ));
+        }
+        let mut rule = SshRule {
+            host: None,
+            network: None,
+            options: SshOptions::default(),
+        };
+        if target.contains('/') {
+            rule.network = Some(target.to_string());
+        } else {
+            rule.host = Some(target.to_string());
+        }
+        for field in fields {
+            let (key, value) = field.split_once('=').ok_or_else(|| {
+                format!(
+                    "invalid ssh option '{}' - expected KEY=VALUE, quote values with commas",
+                    field
+                )
+            })?;
+            let value = value.to_string();
+            match key {
+                "user" => rule.options.user = Some(value),
+                "port" => {
+                    rule.options.port = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid ssh port '{}'", value))?,
+                    )
+                }
+                "identity" => rule.options.identity = Some(value),
+                "jump" => rule.options.jump = Some(value),
+                "option" => rule.options.options.push(value),
+                _ => {
+                    return Err(format!(
+                        "invalid ssh option '{}' - expected user, port, identity, jump or option",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(rule)
+    }
+}
+
+/// How host keys are checked against `~/.ssh/known_hosts`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostKeyPolicy {
+    /// unknown and changed keys fail the host
+    Strict,
+    /// unknown keys are added, changed keys fail the host
+    AcceptNew,
+    /// no check at all - for lab use only
+    Ignore,
+}
+
+impl FromStr for HostKeyPolicy {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "strict" => Ok(HostKeyPolicy::Strict),
+            "accept-new" => Ok(HostKeyPolicy::AcceptNew),
+            "ignore" => Ok(HostKeyPolicy::Ignore),
+            _ => Err(format!(
+                "invalid host key policy '{}' - expected strict, accept-new or ignore",
+                input
+            )),
+        }
+    }
+}
+
+/// Settings of all ssh connections - ssh never prompts, a missing credential or an unknown host
+/// key fails the host instead.
+#[derive(Debug, Clone, Copy)]
+pub struct SshPolicy {
+    pub connect_timeout: Duration,
+    pub host_key: HostKeyPolicy,
+}
+
+impl Default for SshPolicy {
+    fn default() -> Self {
+        SshPolicy {
+            connect_timeout: Duration::from_secs(10),
+            host_key: HostKeyPolicy::Strict,
+        }
+    }
+}
+
+impl SshPolicy {
+    /// Arguments for the `ssh` command line - given after the options of the host, ssh keeps the
+    /// first value of an option so the user can override them.
+    pub fn args(&self) -> Vec<String> {
+        let checking = match self.host_key {
+            HostKeyPolicy::Strict => "yes",
+            HostKeyPolicy::AcceptNew => "accept-new",
+            HostKeyPolicy::Ignore => "no",
+        };
+        let mut options = vec![
+            String::from("BatchMode=yes"),
+            format!("ConnectTimeout={}", self.connect_timeout.as_secs().max(1)),
+            format!("StrictHostKeyChecking={}", checking),
+        ];
+        if self.host_key == HostKeyPolicy::Ignore {
+            options.push(String::from("UserKnownHostsFile=/dev/null"));
+        }
+        options
+            .into_iter()
+            .flat_map(|option| [String::from("-o"), option])
+            .collect()
+    }
+}
+
+/// Selects the ssh options of a host - the defaults are overridden by all matching rules in
+/// their order.
+#[derive(Debug, Clone, Default)]
+pub struct SshConfig {
+    pub defaults: SshOptions,
+    pub rules: Vec<SshRule>,
+    pub policy: SshPolicy,
+}
+
+impl SshConfig {
+    pub fn for_host(&self, host: &str) -> SshOptions {
+        let ips = if self.rules.iter().any(|rule| rule.network.is_some()) {
+            resolve(host)
+        } else {
+            vec![]
+        };
+        let mut options = self.defaults.clone();
+        for rule in self.rules.iter().filter(|rule| rule.matches(host, &ips)) {
+            options.merge(&rule.options);
+        }
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules() {
+        let rule: SshRule = "10.20.0.0/16,user=admin,port=2222,jump=bastion"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.network.as_deref(), Some("10.20.0.0/16"));
+        assert_eq!(rule.options.user.as_deref(), Some("admin"));
+        assert_eq!(rule.options.port, Some(2222));
+        assert_eq!(rule.options.jump.as_deref(), Some("bastion"));
+        assert!("db1,port=ssh".parse::<SshRule>().is_err());
+        assert!("user=admin".parse::<SshRule>().is_err());
+    }
+
+    #[test]
+    fn keeps_quoted_commas() {
+        let rule: SshRule = r#"db1,option="Ciphers=aes128-ctr,aes256-ctr",user=postgres"#
+            .parse()
+            .unwrap();
+        assert_eq!(rule.host.as_deref(), Some("db1"));
+        assert_eq!(rule.options.options, vec!["Ciphers=aes128-ctr,aes256-ctr"]);
+        assert_eq!(rule.options.user.as_deref(), Some("postgres"));
+        assert!("db1,option=Ciphers=aes128-ctr,aes256-ctr"
+            .parse::<SshRule>()
+            .is_err());
+    }
+
+    #[test]
+    fn matches_networks() {
+        let config = SshConfig {
+            rules: vec![
+                "10.20.0.0/16,jump=bastion".parse().unwrap(),
+                "10.20.1.5,user=postgres".parse().unwrap(),
+            ],
+            ..Default::default()
+        };
+        let options = config.for_host("10.20.1.5");
+        assert_eq!(options.jump.as_deref(), Some("bastion"));
+        assert_eq!(options.user.as_deref(), Some("postgres"));
+        assert_eq!(config.for_host("10.30.0.1"), SshOptions::default());
+    }
+
+    #[test]
+    fn overrides_options_of_the_same_key() {
+        let config = SshConfig {
+            defaults: SshOptions {
+                options: vec![
+                    String::from("ConnectTimeout=5"),
+                    String::from("Ciphers=aes256-ctr"),
+                ],
+                ..Default::default()
+            },
+            rules: vec![r#"db1,option="connecttimeout 30""#.parse().unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(
+            config.for_host("db1").options,
+            vec!["Ciphers=aes256-ctr", "connecttimeout 30"]
+        );
+    }
+}
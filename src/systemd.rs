@@ -0,0 +1,56 @@
+/// Find the systemd unit of a process in its `/proc/<pid>/cgroup` paths - the innermost
+/// `.service` or `.scope`, e.g. `0::/system.slice/postgresql.service` or
+/// `0::/user.slice/user-1000.slice/user@1000.service/app.slice/myapp@blue.service`.
+pub fn parse_unit(cgroups: &[String]) -> Option<String> {
+    // cgroup v2 (`0::/...`) or the systemd hierarchy of cgroup v1
+    let path = cgroups
+        .iter()
+        .find(|line| line.starts_with("0::"))
+        .or_else(|| cgroups.iter().find(|line| line.contains(":name=systemd:")))?;
+    path.rsplit('/')
+        .find(|unit| unit.ends_with(".service") || unit.ends_with(".scope"))
+        .map(|unit| unit.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(cgroups: &[&str]) -> Option<String> {
+        let cgroups: Vec<String> = cgroups.iter().map(|line| line.to_string()).collect();
+        parse_unit(&cgroups)
+    }
+
+    #[test]
+    fn finds_innermost_unit() {
+        assert_eq!(
+            unit(&["0::/system.slice/postgresql.service"]).as_deref(),
+            Some("postgresql.service")
+        );
+        assert_eq!(
+            unit(&[
+                "0::/user.slice/user-1000.slice/user@1000.service/app.slice/myapp@blue.service"
+            ])
+            .as_deref(),
+            Some("myapp@blue.service")
+        );
+        assert_eq!(
+            unit(&["0::/user.slice/user-1000.slice/session-3.scope"]).as_deref(),
+            Some("session-3.scope")
+        );
+    }
+
+    #[test]
+    fn reads_the_systemd_hierarchy_of_cgroup_v1() {
+        assert_eq!(
+            unit(&[
+                "12:pids:/system.slice/nginx.service",
+                "1:name=systemd:/system.slice/nginx.service",
+            ])
+            .as_deref(),
+            Some("nginx.service")
+        );
+        assert_eq!(unit(&["0::/"]), None);
+        assert_eq!(unit(&["4:memory:/system.slice/nginx.service"]), None);
+    }
+}
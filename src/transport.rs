@@ -0,0 +1,480 @@
+#[cfg(feature = "native-ssh")]
+use crate::native::NativeTransport;
+use crate::ssh::{SshConfig, SshOptions, SshPolicy};
+use std::fmt;
+use std::io::{self, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: Option<i32>,
+}
+
+/// Why a command could not be run on a host.
+#[derive(Debug)]
+pub enum TransportError {
+    /// no accepted credentials
+    Auth(String),
+    /// the host key is unknown or does not match the known one
+    HostKey(String),
+    Timeout(String),
+    /// the command (or the ssh client) does not exist
+    CommandNotFound(String),
+    /// any other connection or protocol failure
+    Connection(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::Auth(message) => write!(f, "authentication failed: {}", message),
+            TransportError::HostKey(message) => write!(f, "host key rejected: {}", message),
+            TransportError::Timeout(message) => write!(f, "timeout: {}", message),
+            TransportError::CommandNotFound(message) => {
+                write!(f, "command not found: {}", message)
+            }
+            TransportError::Connection(message) => write!(f, "connection failed: {}", message),
+            TransportError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for TransportError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::TimedOut => TransportError::Timeout(err.to_string()),
+            _ => TransportError::Io(err),
+        }
+    }
+}
+
+/// Access path used to run the collector commands on a target host.
+///
+/// The command is a shell command line - the transport decides how it reaches a shell on the
+/// target (remote login shell, local `sh -c`, container exec, ...).
+pub trait Transport: Send + Sync {
+    fn run(&self, command: &str) -> Result<CommandOutput, TransportError>;
+}
+
+/// Shells exit with 127 if a command does not exist.
+pub fn check_status(output: CommandOutput) -> Result<CommandOutput, TransportError> {
+    if output.status == Some(127) {
+        return Err(TransportError::CommandNotFound(
+            output.stderr.trim().to_string(),
+        ));
+    }
+    Ok(output)
+}
+
+/// Timeouts of the commands run on a host.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timeouts {
+    /// limit of each command
+    pub command: Option<Duration>,
+    /// limit of all commands of a host together
+    pub host: Option<Duration>,
+}
+
+/// Time limit of the commands of one host - starts when the transport is created.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limit {
+    command: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl Limit {
+    pub fn new(timeouts: &Timeouts) -> Limit {
+        Limit {
+            command: timeouts.command,
+            deadline: timeouts.host.map(|host| Instant::now() + host),
+        }
+    }
+    /// Time left for the next command - none without limit.
+    pub fn remaining(&self) -> Option<Duration> {
+        let left = self
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        match (self.command, left) {
+            (Some(command), Some(left)) => Some(command.min(left)),
+            (command, left) => command.or(left),
+        }
+    }
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Wait for the command - it is killed when the limit is exceeded.
+fn wait(mut child: Child, limit: &Limit) -> Result<CommandOutput, TransportError> {
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+    let timeout = limit.remaining();
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Some(timeout) = timeout {
+            if started.elapsed() >= timeout {
+                // the command runs in its own process group - killing the group also ends ssh
+                // and the commands started by it, which closes the pipes and ends the readers
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+                let _ = child.wait();
+                let _ = (stdout.join(), stderr.join());
+                return Err(TransportError::Timeout(format!(
+                    "no result after {:.1}s",
+                    timeout.as_secs_f64()
+                )));
+            }
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&stdout.join().unwrap_or_default()).to_string(),
+        stderr: String::from_utf8_lossy(&stderr.join().unwrap_or_default()).to_string(),
+        status: status.code(),
+    })
+}
+
+/// Run a local command without input - no command may wait for a password.
+fn execute(mut cmd: Command, limit: &Limit) -> Result<CommandOutput, TransportError> {
+    log::debug!("Cmd: {:?}", cmd);
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    let child = cmd.spawn().map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => {
+            TransportError::CommandNotFound(cmd.get_program().to_string_lossy().to_string())
+        }
+        _ => TransportError::from(err),
+    })?;
+    check_status(wait(child, limit)?)
+}
+
+/// Quote a string for a POSIX shell.
+pub fn shell_quote(input: &str) -> String {
+    format!("'{}'", input.replace('\'', r"'\''"))
+}
+
+pub struct SshTransport {
+    pub host: String,
+    pub options: SshOptions,
+    pub policy: SshPolicy,
+    pub limit: Limit,
+}
+
+impl SshTransport {
+    /// Arguments for the `ssh` command line before the host - the options of the host come
+    /// first as ssh keeps the first value of an option.
+    fn args(&self) -> Vec<String> {
+        let mut args = self.options.args();
+        args.extend(self.policy.args());
+        args
+    }
+}
+
+impl Transport for SshTransport {
+    fn run(&self, command: &str) -> Result<CommandOutput, TransportError> {
+        let mut cmd = Command::new("ssh");
+        cmd.args(self.args()).arg(&self.host).arg(command);
+        let output = execute(cmd, &self.limit)?;
+        // ssh exits with 255 on its own errors - the reason is only in the message
+        if output.status == Some(255) {
+            let message = format!("{}: {}", self.host, output.stderr.trim());
+            return Err(if output.stderr.contains("Permission denied") {
+                TransportError::Auth(message)
+            } else if output.stderr.contains("Host key verification failed") {
+                TransportError::HostKey(message)
+            } else if output.stderr.contains("timed out") {
+                TransportError::Timeout(message)
+            } else {
+                TransportError::Connection(message)
+            });
+        }
+        Ok(output)
+    }
+}
+
+pub struct LocalTransport {
+    pub limit: Limit,
+}
+
+impl Transport for LocalTransport {
+    fn run(&self, command: &str) -> Result<CommandOutput, TransportError> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        execute(cmd, &self.limit)
+    }
+}
+
+/// Runs commands through a user supplied command template.
+///
+/// `{host}` is replaced by the target host and `{cmd}` by the quoted command. Without a `{cmd}`
+/// placeholder the quoted command is appended, e.g. `docker exec {host} sh -c`.
+pub struct TemplateTransport {
+    pub template: String,
+    pub host: String,
+    pub limit: Limit,
+}
+
+impl Transport for TemplateTransport {
+    fn run(&self, command: &str) -> Result<CommandOutput, TransportError> {
+        let mut line = self.template.replace("{host}", &shell_quote(&self.host));
+        if line.contains("{cmd}") {
+            line = line.replace("{cmd}", &shell_quote(command));
+        } else {
+            line.push(' ');
+            line.push_str(&shell_quote(command));
+        }
+        LocalTransport { limit: self.limit }.run(&line)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportKind {
+    Ssh,
+    /// built-in ssh client - falls back to `ssh` for jump hosts and `-o` options
+    #[cfg(feature = "native-ssh")]
+    Native,
+    Local,
+    Template(String),
+}
+
+impl FromStr for TransportKind {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "ssh" => Ok(TransportKind::Ssh),
+            #[cfg(feature = "native-ssh")]
+            "native" => Ok(TransportKind::Native),
+            #[cfg(not(feature = "native-ssh"))]
+            "native" => Err(String::from(
+                "the native transport requires flowdot built with the native-ssh feature",
+            )),
+            "local" => Ok(TransportKind::Local),
+            _ => match input.strip_prefix("cmd:") {
+                Some(template) if !template.trim().is_empty() => {
+                    Ok(TransportKind::Template(template.to_string()))
+                }
+                _ => Err(format!(
+                    "invalid transport '{}' - expected ssh, native, local or cmd:<template>",
+                    input
+                )),
+            },
+        }
+    }
+}
+
+/// A `[HOST=]TRANSPORT` rule given on the command line.
+#[derive(Debug, Clone)]
+pub struct TransportRule {
+    pub host: Option<String>,
+    pub kind: TransportKind,
+}
+
+impl FromStr for TransportRule {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = match input.parse() {
+            Ok(kind) => return Ok(TransportRule { host: None, kind }),
+            Err(err) => err,
+        };
+        match input.split_once('=') {
+            Some((host, kind)) if !host.is_empty() => Ok(TransportRule {
+                host: Some(host.to_string()),
+                kind: kind.parse()?,
+            }),
+            None => Err(error),
+            _ => Err(format!(
+                "invalid transport rule '{}' - expected [HOST=]TRANSPORT",
+                input
+            )),
+        }
+    }
+}
+
+/// Selects the transport for a host - the last matching host rule wins, hosts without a rule
+/// use the last rule without a host or ssh otherwise.
+#[derive(Debug, Clone)]
+pub struct Transports {
+    rules: Vec<TransportRule>,
+    ssh: SshConfig,
+    timeouts: Timeouts,
+}
+
+impl Transports {
+    pub fn new(rules: Vec<TransportRule>, ssh: SshConfig, timeouts: Timeouts) -> Transports {
+        Transports {
+            rules,
+            ssh,
+            timeouts,
+        }
+    }
+    /// Transport for the local machine.
+    pub fn local(&self) -> Box<dyn Transport> {
+        Box::new(LocalTransport {
+            limit: Limit::new(&self.timeouts),
+        })
+    }
+    pub fn kind_for_host(&self, host: &str) -> TransportKind {
+        let mut kind = TransportKind::Ssh;
+        for rule in self.rules.iter().filter(|rule| rule.host.is_none()) {
+            kind = rule.kind.clone();
+        }
+        for rule in &self.rules {
+            if rule.host.as_deref() == Some(host) {
+                kind = rule.kind.clone();
+            }
+        }
+        kind
+    }
+    fn ssh_options(&self, host: &str, jump: &[String]) -> SshOptions {
+        let mut options = self.ssh.for_host(host);
+        if !jump.is_empty() {
+            options.jump = Some(jump.join(","));
+        }
+        options
+    }
+    /// Jump hosts to reach the hosts seen by `host` through it - `host` and the jump hosts in
+    /// front of it. Empty if `host` is not reached by ssh.
+    ///
+    /// Only login name and port of a jump host are passed on, other options of jump hosts have
+    /// to be set in `~/.ssh/config`.
+    pub fn jump_chain(&self, host: &str, jump: &[String]) -> Vec<String> {
+        match self.kind_for_host(host) {
+            TransportKind::Ssh => {}
+            #[cfg(feature = "native-ssh")]
+            TransportKind::Native => {}
+            _ => return vec![],
+        }
+        let options = self.ssh_options(host, jump);
+        let mut chain: Vec<String> = options
+            .jump
+            .iter()
+            .flat_map(|jump| jump.split(','))
+            .map(String::from)
+            .collect();
+        let mut hop = String::new();
+        if let Some(user) = &options.user {
+            hop.push_str(&format!("{}@", user));
+        }
+        // IPv6 addresses are bracketed to keep them apart from the port
+        if host.contains(':') {
+            hop.push_str(&format!("[{}]", host));
+        } else {
+            hop.push_str(host);
+        }
+        if let Some(port) = options.port {
+            hop.push_str(&format!(":{}", port));
+        }
+        chain.push(hop);
+        chain
+    }
+    /// Transport for a host - `jump` overrides the jump hosts of the ssh options. The host
+    /// timeout starts here.
+    pub fn for_host(&self, host: &str, jump: &[String]) -> Box<dyn Transport> {
+        let kind = self.kind_for_host(host);
+        log::debug!("Transport for {}: {:?} {:?}", host, kind, jump);
+        let limit = Limit::new(&self.timeouts);
+        let policy = self.ssh.policy;
+        match kind {
+            TransportKind::Ssh => Box::new(SshTransport {
+                host: host.to_string(),
+                options: self.ssh_options(host, jump),
+                policy,
+                limit,
+            }),
+            #[cfg(feature = "native-ssh")]
+            TransportKind::Native => {
+                let options = self.ssh_options(host, jump);
+                if options.jump.is_some() || !options.options.is_empty() {
+                    log::warn!(
+                        "Native ssh supports neither jump hosts nor -o options - using ssh for {}",
+                        host
+                    );
+                    Box::new(SshTransport {
+                        host: host.to_string(),
+                        options,
+                        policy,
+                        limit,
+                    })
+                } else {
+                    Box::new(NativeTransport {
+                        host: host.to_string(),
+                        options,
+                        policy,
+                        limit,
+                    })
+                }
+            }
+            TransportKind::Local => Box::new(LocalTransport { limit }),
+            TransportKind::Template(template) => Box::new(TemplateTransport {
+                template,
+                host: host.to_string(),
+                limit,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brackets_ipv6_hops() {
+        let ssh = SshConfig {
+            defaults: SshOptions {
+                user: Some(String::from("admin")),
+                port: Some(2222),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let transports = Transports::new(vec![], ssh, Timeouts::default());
+        assert_eq!(
+            transports.jump_chain("2001:db8::1", &[String::from("bastion")]),
+            vec!["bastion", "admin@[2001:db8::1]:2222"]
+        );
+        assert_eq!(
+            transports.jump_chain("10.0.0.5", &[]),
+            vec!["admin@10.0.0.5:2222"]
+        );
+    }
+
+    #[test]
+    fn passes_user_options_before_the_policy() {
+        let ssh = SshTransport {
+            host: String::from("db1"),
+            options: SshOptions {
+                options: vec![String::from("StrictHostKeyChecking=no")],
+                ..Default::default()
+            },
+            policy: SshPolicy::default(),
+            limit: Limit::default(),
+        };
+        let args = ssh.args();
+        let position = |option: &str| args.iter().position(|arg| arg.starts_with(option));
+        assert!(position("StrictHostKeyChecking=no") < position("StrictHostKeyChecking=yes"));
+        assert!(position("BatchMode=yes").is_some());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::model::UnixSocket;
+use crate::ss::extract_users;
+
+/// Listening and connected unix domain sockets with their processes.
+pub const SECTIONS: [(&str, &str); 1] = [("unix", "ss -xap")];
+
+/// Path and inode of the local and the peer end - the paths may contain spaces, the peer path
+/// starts after the first inode followed by something looking like a path.
+fn split_addresses<'a>(fields: &[&'a str]) -> Option<(String, &'a str, String, &'a str)> {
+    let is_path = |field: &str| field == "*" || field.starts_with('/') || field.starts_with('@');
+    let (peer_inode, fields) = fields.split_last()?;
+    let local_inode = (1..fields.len().checked_sub(1)?).find(|&index| {
+        fields[index].bytes().all(|b| b.is_ascii_digit()) && is_path(fields[index + 1])
+    })?;
+    Some((
+        fields[..local_inode].join(" "),
+        fields[local_inode],
+        fields[local_inode + 1..].join(" "),
+        peer_inode,
+    ))
+}
+
+/// Parse `ss -xap`, e.g.
+/// `u_str ESTAB 0 0 /run/docker.sock 4711 * 4712 users:(("dockerd",pid=1,fd=5))`.
+///
+/// Local and peer address are a path (`*` for unnamed sockets) and an inode each. Sockets that
+/// neither listen nor know their peer are skipped.
+pub fn parse_unix_sockets(content: &str) -> Vec<UnixSocket> {
+    let mut sockets = vec![];
+    for line in content.lines() {
+        // process names may contain spaces too
+        let (addresses, users) = match line.find(" users:(") {
+            Some(index) => line.split_at(index),
+            None => (line, ""),
+        };
+        let fields: Vec<&str> = addresses.split_whitespace().collect();
+        // netid, state, receive and send queue, local path and inode, peer path and inode
+        if fields.len() < 8 || !fields[0].starts_with("u_") {
+            continue;
+        }
+        let (path, inode, _, peer) = match split_addresses(&fields[4..]) {
+            Some(addresses) => addresses,
+            None => {
+                log::warn!("Cannot parse ss line - invalid addresses: {}", line);
+                continue;
+            }
+        };
+        let inode = match inode.parse() {
+            Ok(inode) => inode,
+            Err(_) => {
+                log::warn!("Cannot parse ss line - invalid inode: {}", line);
+                continue;
+            }
+        };
+        let peer = peer.parse().ok().filter(|peer| *peer != 0);
+        let state = fields[1].to_string();
+        if state != "LISTEN" && peer.is_none() {
+            continue;
+        }
+        sockets.push(UnixSocket {
+            kind: fields[0].to_string(),
+            state,
+            path: Some(path).filter(|path| path != "*"),
+            inode,
+            peer,
+            users: extract_users(users),
+        });
+    }
+    sockets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ss -xap` of iproute2 6.1 - a listener, both ends of a connection, a path and a process
+    /// name with spaces, an unconnected datagram socket and an abstract socket.
+    const UNIX: &str = "\
+Netid State  Recv-Q Send-Q                Local Address:Port  Peer Address:Port Process
+u_str LISTEN 0      4096               /run/docker.sock 21050            * 0     users:((\"dockerd\",pid=912,fd=4),(\"systemd\",pid=1,fd=40))
+u_str ESTAB  0      0                  /run/docker.sock 38211            * 38210 users:((\"dockerd\",pid=912,fd=25))
+u_str ESTAB  0      0                                 * 38210            * 38211 users:((\"docker\",pid=4200,fd=3))
+u_str LISTEN 0      128        /run/user/1000/My Socket 5000             * 0     users:((\"my app\",pid=77,fd=3))
+u_dgr UNCONN 0      0                                 * 1234             * 0     users:((\"chronyd\",pid=600,fd=5))
+u_str ESTAB  0      0                @/tmp/.X11-unix/X0 9000             * 9001
+";
+
+    #[test]
+    fn parses_unix_sockets() {
+        let sockets = parse_unix_sockets(UNIX);
+        assert_eq!(sockets.len(), 5);
+        let listener = &sockets[0];
+        assert_eq!(listener.state, "LISTEN");
+        assert_eq!(listener.path.as_deref(), Some("/run/docker.sock"));
+        assert_eq!((listener.inode, listener.peer), (21050, None));
+        assert_eq!(listener.users.len(), 2);
+        assert_eq!((sockets[1].inode, sockets[1].peer), (38211, Some(38210)));
+        assert_eq!(sockets[2].path, None);
+        assert_eq!(sockets[2].users[0].pid, Some(4200));
+    }
+
+    #[test]
+    fn keeps_spaces_in_paths() {
+        let sockets = parse_unix_sockets(UNIX);
+        assert_eq!(sockets[3].path.as_deref(), Some("/run/user/1000/My Socket"));
+        assert_eq!(sockets[3].inode, 5000);
+        assert_eq!(sockets[3].users[0].name, "my app");
+        assert_eq!(sockets[4].path.as_deref(), Some("@/tmp/.X11-unix/X0"));
+        assert!(sockets[4].users.is_empty());
+    }
+}